@@ -0,0 +1,18 @@
+-- SQLite can't alter a CHECK constraint, so the table is rebuilt to allow 'sum' targets.
+CREATE TABLE targets_new (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    target_date DATE,
+    status TEXT CHECK (status IN ('active', 'completed', 'abandoned')) DEFAULT 'active',
+    target_type TEXT CHECK (target_type IN ('count', 'value', 'sum')) NOT NULL,
+    start_value REAL,
+    target_value REAL
+);
+
+INSERT INTO targets_new (id, name, created_at, target_date, status, target_type, start_value, target_value)
+SELECT id, name, created_at, target_date, status, target_type, start_value, target_value FROM targets;
+
+DROP TABLE targets;
+
+ALTER TABLE targets_new RENAME TO targets;
@@ -0,0 +1,27 @@
+-- Rebuilt to allow 'archived' targets and to add the auto_complete flag.
+CREATE TABLE targets_new (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    target_date DATE,
+    status TEXT CHECK (status IN ('active', 'completed', 'abandoned', 'archived')) DEFAULT 'active',
+    target_type TEXT CHECK (target_type IN ('count', 'value', 'sum')) NOT NULL,
+    start_value REAL,
+    target_value REAL,
+    auto_complete BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+INSERT INTO targets_new (id, name, created_at, target_date, status, target_type, start_value, target_value)
+SELECT id, name, created_at, target_date, status, target_type, start_value, target_value FROM targets;
+
+DROP TABLE targets;
+
+ALTER TABLE targets_new RENAME TO targets;
+
+CREATE TABLE target_status_changes (
+    id INTEGER PRIMARY KEY,
+    target_id INTEGER NOT NULL,
+    status TEXT CHECK (status IN ('active', 'completed', 'abandoned', 'archived')) NOT NULL,
+    changed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(target_id) REFERENCES targets(id) ON DELETE CASCADE
+);
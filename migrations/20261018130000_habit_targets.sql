@@ -0,0 +1,2 @@
+-- Targets with a period are habits, where the target value is the quota for each period.
+ALTER TABLE targets ADD COLUMN period TEXT CHECK (period IN ('daily', 'weekly', 'monthly'));
@@ -0,0 +1,4 @@
+-- Targets belong to a year's set of goals, which for existing ones is the year they're due.
+ALTER TABLE targets ADD COLUMN year INTEGER;
+
+UPDATE targets SET year = CAST(strftime('%Y', COALESCE(target_date, created_at)) AS INTEGER);
@@ -0,0 +1,23 @@
+-- A full text index of item names for `nyr search`, kept up to date by triggers. It reads
+-- the names from progress_records rather than keeping a copy of them.
+CREATE VIRTUAL TABLE progress_records_fts USING fts5(
+    item_name,
+    content = 'progress_records',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER progress_records_fts_insert AFTER INSERT ON progress_records BEGIN
+    INSERT INTO progress_records_fts (rowid, item_name) VALUES (new.id, new.item_name);
+END;
+
+CREATE TRIGGER progress_records_fts_delete AFTER DELETE ON progress_records BEGIN
+    INSERT INTO progress_records_fts (progress_records_fts, rowid, item_name) VALUES ('delete', old.id, old.item_name);
+END;
+
+CREATE TRIGGER progress_records_fts_update AFTER UPDATE OF item_name ON progress_records BEGIN
+    INSERT INTO progress_records_fts (progress_records_fts, rowid, item_name) VALUES ('delete', old.id, old.item_name);
+    INSERT INTO progress_records_fts (rowid, item_name) VALUES (new.id, new.item_name);
+END;
+
+INSERT INTO progress_records_fts (progress_records_fts) VALUES ('rebuild');
@@ -1,33 +1,360 @@
-use chrono::Local;
+use chrono::{Days, NaiveDate};
 use iocraft::prelude::*;
+use sqlx::SqlitePool;
 use std::time::Duration;
 
-use crate::{progress_bar, targets};
+use crate::error::{Error, Result};
+use crate::heatmap::{self, DayActivity};
+use crate::{chart, database, duplicates, habits, progress_bar, progress_records, targets, years};
+
+// Lines taken up by everything on the dashboard other than the targets, and by each target
+const DASHBOARD_CHROME_HEIGHT: u16 = 12;
+const TARGET_ROW_HEIGHT: u16 = 5;
+// Lines taken up by the detail pane other than its records
+const DETAIL_CHROME_HEIGHT: u16 = 32;
+const HEATMAP_HEIGHT: u16 = 16;
+// Columns taken up by the heatmap other than its weeks
+const HEATMAP_CHROME_WIDTH: u16 = 12;
+const HEATMAP_MAX_WEEKS: u16 = 53;
+
+const FORM_FIELD_COUNT: usize = 3;
 
 #[derive(Default, Props)]
 pub struct MainProps {
+    pub db: Option<SqlitePool>,
+    /// The status the dashboard was opened with, so refreshing shows the same targets.
+    pub status: Option<targets::TargetStatus>,
+    /// The year whose targets are shown, which is in the title too.
+    pub year: i32,
     pub target_progresses: Vec<targets::TargetProgress>,
 }
 
+// A target along with its records, as shown in the detail pane
+type TargetDetailData = (targets::Target, Vec<progress_records::ProgressRecord>);
+
+async fn get_target_detail(db: &SqlitePool, target_id: &i64) -> Result<TargetDetailData> {
+    let target = targets::get_target(db, target_id).await?;
+    let progress_records = progress_records::get_progress_records_for_target(db, target_id).await?;
+    Ok((target, progress_records))
+}
+
+/// Everything the dashboard reads from the database.
+struct Dashboard {
+    target_progresses: Vec<targets::TargetProgress>,
+    target_detail: Option<TargetDetailData>,
+    /// Records per day over the last year, for the heatmap.
+    activity: Vec<DayActivity>,
+}
+
+/// Reads everything the dashboard shows again, including the detail pane's target if
+/// one is open. A target that's since been deleted just closes the pane.
+async fn get_dashboard(
+    db: &SqlitePool,
+    status: Option<&targets::TargetStatus>,
+    year: i32,
+    detail_target_id: Option<i64>,
+) -> Result<Dashboard> {
+    let target_progresses = targets::get_progress_for_all_targets(db, status, Some(year)).await?;
+    let target_detail = match detail_target_id {
+        Some(target_id) => match get_target_detail(db, &target_id).await {
+            Ok(x) => Some(x),
+            Err(Error::NotFound(_)) => None,
+            Err(x) => return Err(x),
+        },
+        None => None,
+    };
+    let today = years::today();
+    let activity = heatmap::get_daily_activity(
+        db,
+        None,
+        today - Days::new(u64::from(HEATMAP_MAX_WEEKS) * 7),
+        today,
+    )
+    .await?;
+
+    Ok(Dashboard {
+        target_progresses,
+        target_detail,
+        activity,
+    })
+}
+
+/// The parts of the dashboard's state that come from the database.
+#[derive(Clone, Copy)]
+struct DashboardState {
+    target_progresses: State<Vec<targets::TargetProgress>>,
+    selected: State<usize>,
+    detail: State<Option<TargetDetailData>>,
+    activity: State<Vec<DayActivity>>,
+}
+
+impl DashboardState {
+    fn show(mut self, dashboard: Dashboard) {
+        // Targets can drop off the dashboard, e.g. when they're completed or deleted
+        self.selected.set(
+            self.selected
+                .get()
+                .min(dashboard.target_progresses.len().saturating_sub(1)),
+        );
+        self.target_progresses.set(dashboard.target_progresses);
+        if self.detail.read().is_some() {
+            self.detail.set(dashboard.target_detail);
+        }
+        self.activity.set(dashboard.activity);
+    }
+}
+
+/// Turns what was typed into the add form into a progress record's fields, leaving
+/// out anything blank.
+fn parse_record_form(
+    target_type: &targets::TargetType,
+    item_name: &str,
+    value: &str,
+    entry_date: &str,
+) -> Result<(Option<NaiveDate>, Option<f64>, Option<String>)> {
+    let item_name = Some(item_name.trim().to_string()).filter(|x| !x.is_empty());
+    let value = match value.trim() {
+        "" => None,
+        x => Some(
+            x.parse::<f64>()
+                .map_err(|_| Error::Validation(format!("Value must be a number, not \"{}\"", x)))?,
+        ),
+    };
+    let entry_date = match entry_date.trim() {
+        "" => None,
+        x => Some(NaiveDate::parse_from_str(x, "%Y-%m-%d").map_err(|_| {
+            Error::Validation(format!("Date must look like 2025-01-31, not \"{}\"", x))
+        })?),
+    };
+    progress_records::validate_progress_record(target_type, &item_name, &value)?;
+
+    Ok((entry_date, value, item_name))
+}
+
+#[derive(Default, Props)]
+struct TargetDetailPaneProps {
+    target: Option<targets::Target>,
+    habit: Option<habits::HabitProgress>,
+    progress_records: Vec<progress_records::ProgressRecord>,
+}
+
+#[component]
+fn TargetDetailPane<'a>(props: &'a TargetDetailPaneProps) -> impl Into<AnyElement<'a>> {
+    element! {
+        View(flex_direction: FlexDirection::Column, align_items: AlignItems::Center) {
+            targets::TargetDetail(target: props.target.as_ref(), habit: props.habit.clone())
+            progress_records::ProgressRecordsTable(progress_records: Some(&props.progress_records), title: "recent records")
+        }
+    }
+}
+
 #[component]
 fn Main(mut hooks: Hooks, props: &MainProps) -> impl Into<AnyElement<'static>> {
     let (width, height) = hooks.use_terminal_size();
     let mut system = hooks.use_context_mut::<SystemContext>();
-    let mut time = hooks.use_state(|| Local::now());
     let mut should_exit = hooks.use_state(|| false);
+    let target_progresses = hooks.use_state(|| props.target_progresses.clone());
+    let mut selected = hooks.use_state(|| 0usize);
+    let mut scroll = hooks.use_state(|| 0usize);
+    // The target open in the detail pane, along with its records
+    let mut detail = hooks.use_state::<Option<TargetDetailData>, _>(|| None);
+    // The target a progress record is being added to while the add form is open
+    let mut form_target = hooks.use_state::<Option<targets::Target>, _>(|| None);
+    let mut form_field = hooks.use_state(|| 0usize);
+    let mut item_name = hooks.use_state(String::new);
+    let mut value = hooks.use_state(String::new);
+    let mut entry_date = hooks.use_state(String::new);
+    let mut error = hooks.use_state::<Option<String>, _>(|| None);
+    let mut message = hooks.use_state::<Option<String>, _>(|| None);
+    let activity = hooks.use_state::<Vec<DayActivity>, _>(Vec::new);
+    let mut show_heatmap = hooks.use_state(|| false);
+    let dashboard_state = DashboardState {
+        target_progresses,
+        selected,
+        detail,
+        activity,
+    };
 
+    // Pick up changes made elsewhere, e.g. records added in another terminal or by a script
+    let db = props.db.clone();
+    let status = props.status.clone();
+    let year = props.year;
     hooks.use_future(async move {
+        let Some(db) = db else { return };
+        // data_version only changes for writes made through other connections, so this
+        // one is kept out of the pool to watch with
+        let Ok(mut connection) = db.acquire().await else {
+            return;
+        };
+        // Starts out unknown so the first check loads what the targets don't come with,
+        // like the heatmap's activity
+        let mut data_version = None;
         loop {
+            let latest_data_version = database::get_data_version(&mut connection).await.ok();
+            if latest_data_version != data_version {
+                data_version = latest_data_version;
+                let detail_target_id = detail.read().as_ref().map(|(target, _)| target.id);
+                match get_dashboard(&db, status.as_ref(), year, detail_target_id).await {
+                    Ok(x) => dashboard_state.show(x),
+                    Err(x) => error.set(Some(x.to_string())),
+                }
+            }
             smol::Timer::after(Duration::from_secs(1)).await;
-            time.set(Local::now());
         }
     });
 
+    let db = props.db.clone();
+    let mut open_detail = hooks.use_async_handler(move |target_id: i64| {
+        let db = db.clone();
+        async move {
+            let Some(db) = db else { return };
+            match get_target_detail(&db, &target_id).await {
+                Ok(x) => detail.set(Some(x)),
+                Err(x) => error.set(Some(x.to_string())),
+            }
+        }
+    });
+
+    let db = props.db.clone();
+    let mut open_form = hooks.use_async_handler(move |target_id: i64| {
+        let db = db.clone();
+        async move {
+            let Some(db) = db else { return };
+            match targets::get_target(&db, &target_id).await {
+                Ok(target) => {
+                    // Start on the field the target's type needs
+                    form_field.set(match target.target_type {
+                        targets::TargetType::Count => 0,
+                        targets::TargetType::Value | targets::TargetType::Sum => 1,
+                    });
+                    item_name.set(String::new());
+                    value.set(String::new());
+                    entry_date.set(years::today().to_string());
+                    form_target.set(Some(target));
+                }
+                Err(x) => error.set(Some(x.to_string())),
+            }
+        }
+    });
+
+    let db = props.db.clone();
+    let status = props.status.clone();
+    let year = props.year;
+    let mut save_record = hooks.use_async_handler(
+        move |(target, item_name, value, entry_date): (targets::Target, String, String, String)| {
+            let db = db.clone();
+            let status = status.clone();
+            async move {
+                let Some(db) = db else { return };
+                let detail_target_id = detail.read().as_ref().map(|_| target.id);
+                let result = async {
+                    let (entry_date, value, item_name) =
+                        parse_record_form(&target.target_type, &item_name, &value, &entry_date)?;
+                    // Added anyway, as there's no way to say it's a rewatch from the form, and
+                    // a name a typo away is pointed out too
+                    let duplicate = duplicates::get_similar(&db, &target, &item_name, None).await?;
+                    progress_records::create_progress_record(
+                        &db,
+                        &target.id,
+                        &entry_date,
+                        &value,
+                        &item_name,
+                    )
+                    .await?;
+                    let completed = targets::auto_complete_target(&db, &target.id).await?;
+                    let dashboard =
+                        get_dashboard(&db, status.as_ref(), year, detail_target_id).await?;
+                    Ok::<_, Error>((completed, dashboard, duplicate))
+                }
+                .await;
+
+                match result {
+                    Ok((completed, dashboard, duplicate)) => {
+                        dashboard_state.show(dashboard);
+                        message.set(Some(match (completed, duplicate) {
+                            (Some(_), _) => format!(
+                                "Added to \"{}\", which reached 100% and was marked completed",
+                                target.name
+                            ),
+                            (None, Some(duplicate)) => format!(
+                                "Added, though {}",
+                                duplicates::duplicate_message(&target, &duplicate)
+                            ),
+                            (None, None) => format!("Added to \"{}\"", target.name),
+                        }));
+                        form_target.set(None);
+                    }
+                    Err(x) => error.set(Some(x.to_string())),
+                }
+            }
+        },
+    );
+
     hooks.use_terminal_events({
         move |event| match event {
             TerminalEvent::Key(KeyEvent { code, kind, .. }) if kind != KeyEventKind::Release => {
+                let target_count = target_progresses.read().len();
+                let form_open = form_target.read().is_some();
                 match code {
+                    // While the form is open, typing goes to its inputs
+                    KeyCode::Esc if form_open => {
+                        error.set(None);
+                        form_target.set(None);
+                    }
+                    KeyCode::Tab | KeyCode::Down if form_open => {
+                        form_field.set((form_field.get() + 1) % FORM_FIELD_COUNT);
+                    }
+                    KeyCode::BackTab | KeyCode::Up if form_open => {
+                        form_field
+                            .set((form_field.get() + FORM_FIELD_COUNT - 1) % FORM_FIELD_COUNT);
+                    }
+                    KeyCode::Enter if form_open => {
+                        let target = form_target.read().clone();
+                        if let Some(target) = target {
+                            error.set(None);
+                            save_record((
+                                target,
+                                item_name.to_string(),
+                                value.to_string(),
+                                entry_date.to_string(),
+                            ));
+                        }
+                    }
+                    _ if form_open => {}
                     KeyCode::Char('q') => should_exit.set(true),
+                    KeyCode::Char('a') => {
+                        let target_id = match detail.read().as_ref() {
+                            Some((target, _)) => Some(target.id),
+                            None => target_progresses
+                                .read()
+                                .get(selected.get())
+                                .map(|x| x.target_id),
+                        };
+                        if let Some(target_id) = target_id {
+                            error.set(None);
+                            message.set(None);
+                            open_form(target_id);
+                        }
+                    }
+                    KeyCode::Esc => detail.set(None),
+                    _ if detail.read().is_some() => {}
+                    KeyCode::Char('h') => show_heatmap.set(!show_heatmap.get()),
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        selected.set(selected.get().saturating_sub(1));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        selected.set((selected.get() + 1).min(target_count.saturating_sub(1)));
+                    }
+                    KeyCode::Enter => {
+                        let target_id = target_progresses
+                            .read()
+                            .get(selected.get())
+                            .map(|x| x.target_id);
+                        if let Some(target_id) = target_id {
+                            error.set(None);
+                            open_detail(target_id);
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -39,15 +366,88 @@ fn Main(mut hooks: Hooks, props: &MainProps) -> impl Into<AnyElement<'static>> {
         system.exit();
     }
 
-    element! {
-        View(
-            // subtract one in case there's a scrollbar
-            width: width - 1,
-            height,
-            flex_direction: FlexDirection::Column,
-            align_items: AlignItems::Center,
-            justify_content: JustifyContent::Center,
-        ) {
+    // Keep the selected target in view when there are more than fit on screen
+    let heatmap_height = if show_heatmap.get() {
+        HEATMAP_HEIGHT
+    } else {
+        0
+    };
+    let visible_count = (height.saturating_sub(DASHBOARD_CHROME_HEIGHT + heatmap_height)
+        / TARGET_ROW_HEIGHT)
+        .max(1) as usize;
+    if selected.get() < scroll.get() {
+        scroll.set(selected.get());
+    } else if selected.get() >= scroll.get() + visible_count {
+        scroll.set(selected.get() + 1 - visible_count);
+    }
+
+    let target_count = target_progresses.read().len();
+    let footer = match (detail.read().is_some(), target_count > visible_count) {
+        _ if form_target.read().is_some() => String::from(
+            "Press \"Tab\" to move between fields, \"Enter\" to save, \"Esc\" to cancel.",
+        ),
+        (true, _) => String::from("Press \"a\" to add a record, \"Esc\" to go back, \"q\" to quit."),
+        (false, true) => format!(
+            "Showing {}-{} of {}. Press \"↑\"/\"↓\" to select, \"Enter\" for details, \"a\" to add a record, \"h\" for the heatmap, \"q\" to quit.",
+            scroll.get() + 1,
+            (scroll.get() + visible_count).min(target_count),
+            target_count
+        ),
+        (false, false) => {
+            String::from("Press \"↑\"/\"↓\" to select, \"Enter\" for details, \"a\" to add a record, \"h\" for the heatmap, \"q\" to quit.")
+        }
+    };
+
+    let content = match (form_target.read().clone(), detail.read().clone()) {
+        (Some(target), _) => {
+            let fields = [
+                ("item name", item_name, matches!(target.target_type, targets::TargetType::Count)),
+                ("value", value, !matches!(target.target_type, targets::TargetType::Count)),
+                ("date", entry_date, false),
+            ];
+            element! {
+                View(
+                    border_style: BorderStyle::Round,
+                    border_color: Color::Blue,
+                    flex_direction: FlexDirection::Column,
+                    margin_bottom: 1,
+                    padding_top: 1,
+                    padding_left: 2,
+                    padding_right: 2,
+                ) {
+                    View(justify_content: JustifyContent::Center, margin_bottom: 1) {
+                        Text(content: format!("add to {}", target.name), weight: Weight::Bold)
+                    }
+                    #(fields.into_iter().enumerate().map(|(i, (label, mut field, required))| element! {
+                        View(key: label, margin_bottom: 1) {
+                            View(width: 12) {
+                                Text(content: label, weight: Weight::Bold)
+                            }
+                            View(width: 30, background_color: if i == form_field.get() { Some(Color::DarkGrey) } else { None }) {
+                                TextInput(has_focus: i == form_field.get(), value: field.to_string(), on_change: move |x| field.set(x))
+                            }
+                            View(padding_left: 2) {
+                                Text(content: if required { "required" } else { "optional" }, color: Color::Grey)
+                            }
+                        }
+                    }))
+                }
+            }
+            .into_any()
+        }
+        (None, Some((target, progress_records))) => {
+            let habit = habits::HabitProgress::for_target(
+                &target,
+                &progress_records,
+                years::today(),
+            );
+            // Most recent records first, as many as there's room for
+            let room = height.saturating_sub(DETAIL_CHROME_HEIGHT).max(1) as usize;
+            let recent_records: Vec<_> = progress_records.into_iter().rev().take(room).collect();
+            element!(TargetDetailPane(target: Some(target), habit, progress_records: recent_records)).into_any()
+        }
+        (None, None) => element! {
+            View(flex_direction: FlexDirection::Column, align_items: AlignItems::Center) {
             View(
                 border_style: BorderStyle::Round,
                 border_color: Color::Blue,
@@ -61,21 +461,79 @@ fn Main(mut hooks: Hooks, props: &MainProps) -> impl Into<AnyElement<'static>> {
                     View(
                         margin_bottom: 1,
                     ) {
-                        Text(content: "Resolutions 2025", weight: Weight::Bold, align: TextAlign::Center, )
+                        Text(content: format!("Resolutions {}", props.year), weight: Weight::Bold, align: TextAlign::Center, )
                     }
-                    #(props.target_progresses.iter().map(|target_progress| element! {
-                        View {
-                            progress_bar::StaticProgressBar(progress_percentage: target_progress.percentage, target: format!("{:.0}", target_progress.target_value), title: target_progress.name.clone())
+                    #(target_progresses.read().iter().enumerate().skip(scroll.get()).take(visible_count).map(|(i, target_progress)| element! {
+                        View(key: target_progress.target_id, margin_bottom: 1) {
+                            View(width: 2, padding_top: 1) {
+                                Text(content: if i == selected.get() { "›" } else { " " }, color: Color::Blue, weight: Weight::Bold)
+                            }
+                            View(flex_direction: FlexDirection::Column) {
+                                View {
+                                progress_bar::StaticProgressBar(
+                                    progress_percentage: target_progress.percentage,
+                                    target: format!("{:.0}/{:.0}", target_progress.current_value, target_progress.target_value),
+                                    title: target_progress.name.clone(),
+                                )
+                                View(width: chart::SPARKLINE_WIDTH as u32 + 1, padding_left: 1, padding_top: 1) {
+                                    Text(content: target_progress.sparkline.clone(), color: Color::Green)
+                                }
+                                }
+                                View(padding_left: 15) {
+                                    #(match &target_progress.habit {
+                                        Some(habit) => element!(Text(content: habit.summary(), color: if habit.is_on_streak() { Color::Green } else { Color::Red })),
+                                        None => element!(Text(content: target_progress.pace.summary(), color: if target_progress.pace.is_ahead() { Color::Green } else { Color::Red })),
+                                    })
+                                }
+                            }
                         }
                     }))
 
                 }
             }
-            Text(content: "Press \"q\" to quit.")
+            #(show_heatmap.get().then(|| {
+                // As many weeks as fit across, up to a year
+                let weeks = (width.saturating_sub(HEATMAP_CHROME_WIDTH) / heatmap::WEEK_WIDTH as u16)
+                    .clamp(1, HEATMAP_MAX_WEEKS);
+                let to = years::today();
+                let from = habits::Period::Weekly.start_of(to) - Days::new(u64::from(weeks - 1) * 7);
+                element! {
+                    heatmap::Heatmap(days: activity.read().clone(), from, to, title: "activity")
+                }
+            }))
+            }
+        }
+        .into_any(),
+    };
+
+    element! {
+        View(
+            // subtract one in case there's a scrollbar
+            width: width - 1,
+            height,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+        ) {
+            #(content)
+            #(error.read().clone().map(|error| element! {
+                Text(content: error, color: Color::Red)
+            }))
+            #(message.read().clone().map(|message| element! {
+                Text(content: message, color: Color::Green)
+            }))
+            Text(content: footer)
         }
     }
 }
 
-pub fn run_app(target_progresses: Vec<targets::TargetProgress>) {
-    smol::block_on(element!(Main(target_progresses)).fullscreen()).unwrap();
+pub async fn run_app(
+    db: SqlitePool,
+    status: Option<targets::TargetStatus>,
+    year: i32,
+    target_progresses: Vec<targets::TargetProgress>,
+) -> std::io::Result<()> {
+    element!(Main(db, status, year, target_progresses))
+        .fullscreen()
+        .await
 }
@@ -0,0 +1,466 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use iocraft::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Pool, Sqlite, SqliteConnection};
+
+use crate::error::{Error, Result};
+use crate::habits::{self, Period};
+use crate::progress_records::{self, ProgressRecord};
+use crate::targets::{TargetStatus, TargetType};
+
+/// The version of the document `nyr export` writes. It goes up when the document changes
+/// in a way older versions of nyr can't read, and `read_backup` has to keep reading the
+/// older versions.
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Everything in a database, as written by `nyr export` and read by `nyr import json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub exported_at: NaiveDateTime,
+    pub targets: Vec<BackupTarget>,
+    pub progress_records: Vec<BackupProgressRecord>,
+}
+
+/// A target as it's stored, without anything worked out from its records.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BackupTarget {
+    /// Only used to match up the records, targets get new ids when they're imported.
+    pub id: i64,
+    pub name: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub target_date: NaiveDate,
+    pub status: TargetStatus,
+    pub target_type: TargetType,
+    pub start_value: f64,
+    pub target_value: f64,
+    pub auto_complete: bool,
+    pub period: Option<Period>,
+    pub year: i32,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub status_changes: Vec<BackupStatusChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BackupStatusChange {
+    #[serde(skip)]
+    pub target_id: i64,
+    pub status: TargetStatus,
+    pub changed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BackupProgressRecord {
+    pub id: i64,
+    pub target_id: i64,
+    pub created_at: Option<NaiveDateTime>,
+    pub entry_date: NaiveDate,
+    pub value: Option<f64>,
+    pub item_name: Option<String>,
+}
+
+pub async fn get_backup(db: &Pool<Sqlite>) -> Result<Backup> {
+    let mut targets = sqlx::query_as::<_, BackupTarget>(
+        "SELECT id, name, created_at, target_date, status, target_type, start_value, target_value, auto_complete, period, year
+            FROM targets ORDER BY id",
+    )
+    .fetch_all(db)
+    .await?;
+    let status_changes = sqlx::query_as::<_, BackupStatusChange>(
+        "SELECT target_id, status, changed_at FROM target_status_changes ORDER BY id",
+    )
+    .fetch_all(db)
+    .await?;
+    for status_change in status_changes {
+        if let Some(target) = targets.iter_mut().find(|x| x.id == status_change.target_id) {
+            target.status_changes.push(status_change);
+        }
+    }
+    let progress_records = sqlx::query_as::<_, BackupProgressRecord>(
+        "SELECT id, target_id, created_at, entry_date, value, item_name FROM progress_records ORDER BY id",
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(Backup {
+        version: BACKUP_VERSION,
+        exported_at: chrono::Utc::now().naive_utc(),
+        targets,
+        progress_records,
+    })
+}
+
+/// Reads a backup, checking it's a version this nyr understands and that it hangs together:
+/// every record is for a target in it and has what that target's type needs.
+pub fn read_backup(json: &str) -> Result<Backup> {
+    let invalid = |error: String| Error::Validation(format!("The backup isn't valid: {}", error));
+
+    let document: serde_json::Value =
+        serde_json::from_str(json).map_err(|x| invalid(x.to_string()))?;
+    match document.get("version").and_then(|x| x.as_u64()) {
+        None => return Err(invalid(String::from("it has no version, so it wasn't made by `nyr export`"))),
+        Some(version) if version > u64::from(BACKUP_VERSION) => {
+            return Err(Error::Validation(format!(
+                "The backup is version {}, which is newer than this version of nyr can read ({}), please upgrade",
+                version, BACKUP_VERSION
+            )))
+        }
+        Some(_) => {}
+    }
+    let backup: Backup = serde_json::from_value(document).map_err(|x| invalid(x.to_string()))?;
+
+    let mut target_types = HashMap::new();
+    for target in &backup.targets {
+        if target_types
+            .insert(target.id, &target.target_type)
+            .is_some()
+        {
+            return Err(invalid(format!(
+                "more than one target has id {}",
+                target.id
+            )));
+        }
+        if target.period.is_some() {
+            habits::validate_habit(
+                &target.target_type,
+                target.start_value,
+                target.auto_complete,
+            )
+            .map_err(|x| invalid(format!("target {}: {}", target.id, x)))?;
+        }
+    }
+    for progress_record in &backup.progress_records {
+        let Some(target_type) = target_types.get(&progress_record.target_id) else {
+            return Err(invalid(format!(
+                "progress record {} is for target {}, which isn't in the backup",
+                progress_record.id, progress_record.target_id
+            )));
+        };
+        progress_records::validate_progress_record(
+            target_type,
+            &progress_record.item_name,
+            &progress_record.value,
+        )
+        .map_err(|x| invalid(format!("progress record {}: {}", progress_record.id, x)))?;
+    }
+    Ok(backup)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestoreMode {
+    /// Adds to what's there. Targets with the same name and year are merged, and records
+    /// they already have are skipped, so restoring the same backup twice changes nothing.
+    /// Targets can only be merged if they're the same type and have the same period.
+    Merge,
+    /// Deletes everything first.
+    Replace,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RestoreSummary {
+    pub targets_created: usize,
+    pub targets_merged: usize,
+    pub progress_records_created: usize,
+    /// Records a merged target already had.
+    pub progress_records_skipped: usize,
+}
+
+/// E.g. "a count target" or "a weekly sum habit".
+fn describe_kind(target_type: &TargetType, period: Option<Period>) -> String {
+    match period {
+        Some(period) => format!("a {} {} habit", period, target_type),
+        None => format!("a {} target", target_type),
+    }
+}
+
+async fn insert_target(connection: &mut SqliteConnection, target: &BackupTarget) -> Result<i64> {
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO targets (name, created_at, target_date, status, target_type, start_value, target_value, auto_complete, period, year)
+            VALUES ($1, COALESCE($2, CURRENT_TIMESTAMP), $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING id",
+    )
+    .bind(&target.name)
+    .bind(target.created_at)
+    .bind(target.target_date)
+    .bind(&target.status)
+    .bind(&target.target_type)
+    .bind(target.start_value)
+    .bind(target.target_value)
+    .bind(target.auto_complete)
+    .bind(target.period)
+    .bind(target.year)
+    .fetch_one(&mut *connection)
+    .await?;
+
+    for status_change in &target.status_changes {
+        sqlx::query(
+            "INSERT INTO target_status_changes (target_id, status, changed_at)
+                VALUES ($1, $2, COALESCE($3, CURRENT_TIMESTAMP))",
+        )
+        .bind(id)
+        .bind(&status_change.status)
+        .bind(status_change.changed_at)
+        .execute(&mut *connection)
+        .await?;
+    }
+    Ok(id)
+}
+
+/// Imports a backup in one transaction, giving its targets and records new ids. A dry run
+/// rolls it back once it's worked out what would change.
+pub async fn restore(
+    db: &Pool<Sqlite>,
+    backup: &Backup,
+    mode: RestoreMode,
+    dry_run: bool,
+) -> Result<RestoreSummary> {
+    let mut summary = RestoreSummary::default();
+    let mut tx = db.begin().await?;
+
+    if mode == RestoreMode::Replace {
+        sqlx::query("DELETE FROM target_status_changes")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM progress_records")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM targets").execute(&mut *tx).await?;
+    }
+
+    // Existing targets by lower case name and year, with what they'd need to match to merge
+    let mut existing_targets: HashMap<(String, i32), (i64, TargetType, Option<Period>)> =
+        sqlx::query_as::<_, (i64, String, i32, TargetType, Option<Period>)>(
+            "SELECT id, name, year, target_type, period FROM targets",
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|(id, name, year, target_type, period)| {
+            ((name.to_lowercase(), year), (id, target_type, period))
+        })
+        .collect();
+
+    // Backup target ids to the ids they have now
+    let mut target_ids: HashMap<i64, i64> = HashMap::new();
+    let mut merged_target_ids = HashSet::new();
+    for target in &backup.targets {
+        let key = (target.name.to_lowercase(), target.year);
+        let id = match existing_targets.get(&key) {
+            // Its records wouldn't make sense for the target they'd be merged into
+            Some((_, target_type, period))
+                if *target_type != target.target_type || *period != target.period =>
+            {
+                return Err(Error::Validation(format!(
+                    "\"{}\" in the backup is {}, but the {} target it would be merged into is {}, so rename one of them first",
+                    target.name,
+                    describe_kind(&target.target_type, target.period),
+                    target.year,
+                    describe_kind(target_type, *period)
+                )));
+            }
+            Some((id, _, _)) => {
+                summary.targets_merged += 1;
+                merged_target_ids.insert(*id);
+                *id
+            }
+            None => {
+                summary.targets_created += 1;
+                let id = insert_target(&mut tx, target).await?;
+                existing_targets.insert(key, (id, target.target_type.clone(), target.period));
+                id
+            }
+        };
+        target_ids.insert(target.id, id);
+    }
+
+    // What merged targets already have, to skip
+    let mut existing_records: HashSet<(i64, NaiveDate, Option<String>, Option<u64>)> =
+        HashSet::new();
+    for target_id in &merged_target_ids {
+        let progress_records = sqlx::query_as::<_, ProgressRecord>(
+            "SELECT * FROM progress_records WHERE target_id = $1",
+        )
+        .bind(target_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        existing_records.extend(progress_records.into_iter().map(|x| {
+            (
+                x.target_id,
+                x.entry_date,
+                x.item_name,
+                x.value.map(f64::to_bits),
+            )
+        }));
+    }
+
+    for progress_record in &backup.progress_records {
+        let target_id = target_ids[&progress_record.target_id];
+        let key = (
+            target_id,
+            progress_record.entry_date,
+            progress_record.item_name.clone(),
+            progress_record.value.map(f64::to_bits),
+        );
+        if existing_records.contains(&key) {
+            summary.progress_records_skipped += 1;
+            continue;
+        }
+        sqlx::query(
+            "INSERT INTO progress_records (target_id, created_at, entry_date, value, item_name)
+                VALUES ($1, COALESCE($2, CURRENT_TIMESTAMP), $3, $4, $5)",
+        )
+        .bind(target_id)
+        .bind(progress_record.created_at)
+        .bind(progress_record.entry_date)
+        .bind(progress_record.value)
+        .bind(&progress_record.item_name)
+        .execute(&mut *tx)
+        .await?;
+        summary.progress_records_created += 1;
+    }
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    Ok(summary)
+}
+
+#[derive(Default, Props)]
+pub struct RestoreSummaryProps<'a> {
+    pub summary: Option<&'a RestoreSummary>,
+    pub title: &'a str,
+}
+
+#[component]
+pub fn RestoreSummaryTable<'a>(props: &RestoreSummaryProps<'a>) -> impl Into<AnyElement<'a>> {
+    let rows = match props.summary {
+        Some(summary) => vec![
+            ("targets created", summary.targets_created),
+            ("targets merged", summary.targets_merged),
+            ("records created", summary.progress_records_created),
+            ("records skipped", summary.progress_records_skipped),
+        ],
+        None => vec![],
+    };
+
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            padding_left: 2,
+            padding_right: 2,
+            flex_direction: FlexDirection::Column,
+            width: 50,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom: 1) {
+                Text(content: props.title, weight: Weight::Bold)
+            }
+            #(rows.into_iter().map(|(label, count)| element! {
+                View {
+                    View(width: 20) {
+                        Text(content: label, weight: Weight::Bold)
+                    }
+                    Text(content: count.to_string())
+                }
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::targets::{self, NewTarget};
+
+    async fn create_target(
+        db: &Pool<Sqlite>,
+        name: &str,
+        target_type: TargetType,
+        period: Option<Period>,
+    ) {
+        let new_target = NewTarget {
+            name: String::from(name),
+            target_date: NaiveDate::from_ymd_opt(2026, 12, 31),
+            target_type,
+            start_value: None,
+            target_value: 10.0,
+            auto_complete: false,
+            period,
+            year: None,
+            created_at: None,
+        };
+        targets::create_target(db, &new_target).await.unwrap();
+    }
+
+    /// A backup of one target, with one record.
+    fn backup(name: &str, target_type: TargetType, period: Option<Period>) -> Backup {
+        let entry_date = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        Backup {
+            version: BACKUP_VERSION,
+            exported_at: entry_date.and_hms_opt(0, 0, 0).unwrap(),
+            progress_records: vec![BackupProgressRecord {
+                id: 1,
+                target_id: 1,
+                created_at: None,
+                entry_date,
+                value: Some(5.0),
+                item_name: Some(String::from("Zodiac")),
+            }],
+            targets: vec![BackupTarget {
+                id: 1,
+                name: String::from(name),
+                created_at: None,
+                target_date: NaiveDate::from_ymd_opt(2026, 12, 31).unwrap(),
+                status: TargetStatus::Active,
+                target_type,
+                start_value: 0.0,
+                target_value: 10.0,
+                auto_complete: false,
+                period,
+                year: 2026,
+                status_changes: Vec::new(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn merge_refuses_targets_that_dont_match() {
+        let db = database::open_test_db().await.unwrap();
+        create_target(&db, "weight", TargetType::Value, None).await;
+        create_target(&db, "gym", TargetType::Count, Some(Period::Weekly)).await;
+
+        for backup in [
+            backup("Weight", TargetType::Count, None),
+            backup("gym", TargetType::Count, None),
+            backup("gym", TargetType::Count, Some(Period::Monthly)),
+        ] {
+            let result = restore(&db, &backup, RestoreMode::Merge, false).await;
+            assert!(matches!(result, Err(Error::Validation(_))));
+        }
+        let progress_records =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM progress_records")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(progress_records, 0);
+
+        let summary = restore(
+            &db,
+            &backup("Weight", TargetType::Value, None),
+            RestoreMode::Merge,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.targets_merged, 1);
+        assert_eq!(summary.progress_records_created, 1);
+    }
+}
@@ -0,0 +1,244 @@
+use chrono::{Days, NaiveDate};
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::error::{Error, Result};
+use crate::habits;
+use crate::pace;
+use crate::progress_records::{self, ProgressRecord};
+use crate::targets::{self, Target, TargetType};
+
+const CHART_WIDTH: usize = 60;
+const CHART_HEIGHT: usize = 15;
+const AXIS_LABEL_WIDTH: usize = 10;
+/// Characters wide the dashboard's sparklines are, one for each point in time.
+pub const SPARKLINE_WIDTH: usize = 16;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Where a target had got to by the end of a day with records.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProgressPoint {
+    pub entry_date: NaiveDate,
+    pub value: f64,
+}
+
+/// Replays a target's records, which have to be ordered by entry date, working out its
+/// value after each day the same way its current value is.
+pub fn progress_series(target: &Target, progress_records: &[ProgressRecord]) -> Vec<ProgressPoint> {
+    let mut series: Vec<ProgressPoint> = Vec::new();
+    let mut value = target.start_value;
+    for progress_record in progress_records {
+        value = match target.target_type {
+            TargetType::Count => value + 1.0,
+            TargetType::Sum => value + progress_record.value.unwrap_or(0.0),
+            TargetType::Value => progress_record.value.unwrap_or(value),
+        };
+        match series.last_mut() {
+            Some(point) if point.entry_date == progress_record.entry_date => point.value = value,
+            _ => series.push(ProgressPoint {
+                entry_date: progress_record.entry_date,
+                value,
+            }),
+        }
+    }
+    series
+}
+
+pub async fn get_progress_series(db: &Pool<Sqlite>, target: &Target) -> Result<Vec<ProgressPoint>> {
+    if target.period.is_some() {
+        return Err(Error::Validation(format!(
+            "\"{}\" is a habit, which starts again each period, so try `nyr heatmap --target {}` instead",
+            target.name, target.id
+        )));
+    }
+    let progress_records =
+        progress_records::get_progress_records_for_target(db, &target.id).await?;
+    Ok(progress_series(target, &progress_records))
+}
+
+/// The target's value at the end of `date`, going by its series.
+pub fn value_on(target: &Target, series: &[ProgressPoint], date: NaiveDate) -> f64 {
+    series
+        .iter()
+        .take_while(|x| x.entry_date <= date)
+        .last()
+        .map_or(target.start_value, |x| x.value)
+}
+
+// `count` dates spread evenly from `from` to `to`, both included
+fn spread_dates(from: NaiveDate, to: NaiveDate, count: usize) -> Vec<NaiveDate> {
+    let days = (to - from).num_days().max(0) as usize;
+    (0..count)
+        .map(|i| from + Days::new((days * i / (count - 1).max(1)) as u64))
+        .collect()
+}
+
+fn spark(fraction: f64) -> char {
+    let level = (fraction.clamp(0.0, 1.0) * (SPARKS.len() - 1) as f64).round() as usize;
+    SPARKS[level]
+}
+
+/// A line of block characters showing how the target's progress has grown up to
+/// `today`. For habits each character is a period instead, against its quota.
+pub fn sparkline(target: &Target, progress_records: &[ProgressRecord], today: NaiveDate) -> String {
+    let first_date = target.first_date(progress_records);
+
+    if let Some(period) = target.period {
+        let period_values = habits::period_totals(target, period, progress_records);
+        let mut period_starts = vec![period.start_of(first_date.min(today))];
+        while let Some(start) = period_starts.last().map(|x| period.next(*x)) {
+            if start > today {
+                break;
+            }
+            period_starts.push(start);
+        }
+        return period_starts
+            .iter()
+            .skip(period_starts.len().saturating_sub(SPARKLINE_WIDTH))
+            .map(|start| {
+                let value = period_values.get(start).copied().unwrap_or(0.0);
+                spark(if target.target_value > 0.0 {
+                    value / target.target_value
+                } else {
+                    1.0
+                })
+            })
+            .collect();
+    }
+
+    let series = progress_series(target, progress_records);
+    spread_dates(first_date, today.max(first_date), SPARKLINE_WIDTH)
+        .into_iter()
+        .map(|date| {
+            let percentage = targets::progress_percentage(
+                target.start_value,
+                target.target_value,
+                value_on(target, &series, date),
+            );
+            spark(percentage / 100.0)
+        })
+        .collect()
+}
+
+#[derive(Default, Props)]
+pub struct BurnUpChartProps {
+    pub target: Option<Target>,
+    pub series: Vec<ProgressPoint>,
+    pub today: Option<NaiveDate>,
+}
+
+/// A line chart of the target's value over time up to today, against the ideal pace from
+/// its creation to its target date.
+#[component]
+pub fn BurnUpChart(props: &BurnUpChartProps) -> impl Into<AnyElement<'static>> {
+    let (Some(target), Some(today)) = (&props.target, props.today) else {
+        return element!(View).into_any();
+    };
+
+    let from = props
+        .series
+        .first()
+        .map(|x| x.entry_date)
+        .into_iter()
+        .chain([target.created_at.date()])
+        .min()
+        .unwrap();
+    let to = props
+        .series
+        .last()
+        .map(|x| x.entry_date)
+        .into_iter()
+        .chain([target.target_date, today.min(target.target_date)])
+        .max()
+        .unwrap()
+        .max(from);
+    let dates = spread_dates(from, to, CHART_WIDTH);
+
+    let actual: Vec<Option<f64>> = dates
+        .iter()
+        .map(|date| (*date <= today).then(|| value_on(target, &props.series, *date)))
+        .collect();
+    let ideal: Vec<f64> = dates
+        .iter()
+        .map(|date| pace::expected_value(target, *date))
+        .collect();
+
+    let values = actual.iter().flatten().chain(&ideal).copied();
+    let low = values.clone().fold(f64::INFINITY, f64::min);
+    let mut high = values.fold(f64::NEG_INFINITY, f64::max);
+    if high <= low {
+        high = low + 1.0;
+    }
+    let row_of =
+        |value: f64| ((value - low) / (high - low) * (CHART_HEIGHT - 1) as f64).round() as usize;
+
+    let mut cells = vec![vec![(' ', Color::Reset); CHART_WIDTH]; CHART_HEIGHT];
+    for (column, value) in ideal.iter().enumerate() {
+        cells[CHART_HEIGHT - 1 - row_of(*value)][column] = ('·', Color::Grey);
+    }
+    for (column, value) in actual.iter().enumerate() {
+        if let Some(value) = value {
+            cells[CHART_HEIGHT - 1 - row_of(*value)][column] = ('●', Color::Green);
+        }
+    }
+
+    // Runs of the same colour make up one piece of text, rather than a text per cell
+    let rows: Vec<Vec<(String, Color)>> = cells
+        .into_iter()
+        .map(|row| {
+            let mut segments: Vec<(String, Color)> = Vec::new();
+            for (character, color) in row {
+                match segments.last_mut() {
+                    Some((text, last_color)) if *last_color == color => text.push(character),
+                    _ => segments.push((character.to_string(), color)),
+                }
+            }
+            segments
+        })
+        .collect();
+    let axis_label = |row: usize| match row {
+        0 => format!("{:.1}", high),
+        x if x == CHART_HEIGHT - 1 => format!("{:.1}", low),
+        _ => String::new(),
+    };
+
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            padding_left: 2,
+            padding_right: 2,
+            flex_direction: FlexDirection::Column,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(justify_content: JustifyContent::Center, margin_bottom: 1) {
+                Text(content: target.name.clone(), weight: Weight::Bold)
+            }
+            #(rows.into_iter().enumerate().map(|(row, segments)| element! {
+                View(key: row) {
+                    View(width: AXIS_LABEL_WIDTH as u32) {
+                        Text(content: axis_label(row), color: Color::Grey)
+                    }
+                    #(segments.into_iter().enumerate().map(|(i, (text, color))| element! {
+                        View(key: i, width: text.chars().count() as u32) {
+                            Text(content: text, color)
+                        }
+                    }))
+                }
+            }))
+            View(padding_left: AXIS_LABEL_WIDTH as u32, width: (AXIS_LABEL_WIDTH + CHART_WIDTH) as u32, justify_content: JustifyContent::SpaceBetween) {
+                Text(content: from.to_string(), color: Color::Grey)
+                Text(content: to.to_string(), color: Color::Grey)
+            }
+            View(margin_top: 1) {
+                View(margin_right: 2) {
+                    Text(content: "● actual", color: Color::Green)
+                }
+                Text(content: "· ideal pace to the target date", color: Color::Grey)
+            }
+        }
+    }
+    .into_any()
+}
@@ -0,0 +1,406 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use directories::ProjectDirs;
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{
+    migrate::{MigrateDatabase, Migrator},
+    sqlite::SqliteConnectOptions,
+    Connection, FromRow, Sqlite, SqliteConnection, SqlitePool,
+};
+
+use crate::error::{Error, Result};
+
+// Migrations are built into the binary so upgrading nyr brings its schema changes with it.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+const DEFAULT_PROFILE: &str = "default";
+
+fn get_data_dir() -> Result<PathBuf> {
+    let proj_dirs = ProjectDirs::from("", "", "nyr").ok_or_else(|| {
+        Error::Io(std::io::Error::other(
+            "Failed to determine project directories",
+        ))
+    })?;
+
+    let data_dir = proj_dirs.data_dir();
+    std::fs::create_dir_all(data_dir)?;
+
+    Ok(data_dir.to_path_buf())
+}
+
+fn get_profile_path(data_dir: &Path, profile: &str) -> Result<PathBuf> {
+    if profile == DEFAULT_PROFILE {
+        return Ok(data_dir.join("database.sqlite"));
+    }
+
+    let is_valid = !profile.is_empty()
+        && profile
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_');
+    if !is_valid {
+        return Err(Error::Validation(format!(
+            "Profile names can only contain letters, numbers, \"-\" and \"_\", not \"{}\"",
+            profile
+        )));
+    }
+
+    Ok(data_dir
+        .join("profiles")
+        .join(format!("{}.sqlite", profile)))
+}
+
+/// Works out which database file to use. A profile wins over a path, which only happens
+/// when both are set in the environment, and with neither the default profile is used.
+fn get_db_path(data_dir: &Path, db: Option<&Path>, profile: Option<&str>) -> Result<PathBuf> {
+    match (profile, db) {
+        (Some(profile), _) => get_profile_path(data_dir, profile),
+        (None, Some(db)) => Ok(db.to_path_buf()),
+        (None, None) => get_profile_path(data_dir, DEFAULT_PROFILE),
+    }
+}
+
+fn get_db_url(db: Option<&Path>, profile: Option<&str>) -> Result<String> {
+    let db_path = get_db_path(&get_data_dir()?, db, profile)?;
+    if let Some(parent) = db_path.parent().filter(|x| !x.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(format!("sqlite:{}", db_path.display()))
+}
+
+async fn ensure_db_exists(
+    db: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<SqliteConnectOptions> {
+    let db_url = get_db_url(db, profile)?;
+
+    if !Sqlite::database_exists(&db_url).await.unwrap_or(false) {
+        log::debug!("Creating database {}", db_url);
+        Sqlite::create_database(&db_url).await.map_err(|error| {
+            log::error!("Failed to create database: {}", error);
+            error
+        })?;
+        log::debug!("Create db success");
+    } else {
+        log::debug!("Database already exists");
+    }
+
+    Ok(SqliteConnectOptions::from_str(&db_url)?)
+}
+
+pub async fn ensure_db_and_tables_exist(
+    db: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<SqlitePool> {
+    let options = ensure_db_exists(db, profile).await?;
+
+    // Migrations that rebuild tables have to run with foreign keys off, as SQLite
+    // recommends, otherwise dropping the old table trips over the rows pointing at it.
+    let mut migration_connection =
+        SqliteConnection::connect_with(&options.clone().foreign_keys(false)).await?;
+    match MIGRATOR.run(&mut migration_connection).await {
+        Ok(_) => log::debug!("Migration successful"),
+        Err(error) => {
+            log::error!("Migration failed: {}", error);
+            return Err(error.into());
+        }
+    }
+    migration_connection.close().await?;
+
+    let db = SqlitePool::connect_with(options.foreign_keys(true))
+        .await
+        .map_err(|error| {
+            log::error!("Failed to connect to database: {}", error);
+            error
+        })?;
+
+    Ok(db)
+}
+
+/// An empty database in memory with every migration applied, for tests. There's only one
+/// connection, as each connection to `sqlite::memory:` gets a database of its own.
+#[cfg(test)]
+pub async fn open_test_db() -> Result<SqlitePool> {
+    let options = SqliteConnectOptions::from_str("sqlite::memory:")?.foreign_keys(true);
+    let db = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await?;
+    MIGRATOR.run(&db).await?;
+    Ok(db)
+}
+
+/// Changes whenever another connection commits a write to the database, so it can be
+/// polled to notice changes made by other nyr processes.
+pub async fn get_data_version(connection: &mut SqliteConnection) -> Result<i64> {
+    Ok(sqlx::query_scalar::<_, i64>("PRAGMA data_version")
+        .fetch_one(connection)
+        .await?)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the migration built into nyr has changed since.
+    ChecksumMismatch,
+    /// Applied by a newer version of nyr that this one doesn't know about.
+    Unknown,
+}
+
+impl std::fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MigrationState::Applied => write!(f, "applied"),
+            MigrationState::Pending => write!(f, "pending"),
+            MigrationState::ChecksumMismatch => write!(f, "checksum mismatch"),
+            MigrationState::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub installed_on: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(FromRow)]
+struct AppliedMigration {
+    version: i64,
+    description: String,
+    checksum: Vec<u8>,
+    installed_on: chrono::NaiveDateTime,
+}
+
+async fn get_applied_migrations(db_path: &Path) -> Result<Vec<AppliedMigration>> {
+    let options = SqliteConnectOptions::new()
+        .filename(db_path)
+        .read_only(true);
+    let mut connection = SqliteConnection::connect_with(&options).await?;
+
+    let has_migrations_table = sqlx::query_scalar::<_, bool>(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_one(&mut connection)
+    .await?;
+    let applied = if has_migrations_table {
+        sqlx::query_as::<_, AppliedMigration>(
+            "SELECT version, description, checksum, installed_on FROM _sqlx_migrations
+                WHERE success ORDER BY version",
+        )
+        .fetch_all(&mut connection)
+        .await?
+    } else {
+        Vec::new()
+    };
+    connection.close().await?;
+    Ok(applied)
+}
+
+/// Compares the migrations built into nyr with the ones applied to the database,
+/// without applying anything. A database that doesn't exist yet isn't created, and
+/// has every migration pending.
+pub async fn get_migration_statuses(
+    db: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<Vec<MigrationStatus>> {
+    let db_path = get_db_path(&get_data_dir()?, db, profile)?;
+    let applied = if db_path.exists() {
+        get_applied_migrations(&db_path).await?
+    } else {
+        Vec::new()
+    };
+
+    let mut statuses: Vec<MigrationStatus> = MIGRATOR
+        .iter()
+        .map(|migration| {
+            let applied_migration = applied.iter().find(|x| x.version == migration.version);
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state: match applied_migration {
+                    None => MigrationState::Pending,
+                    Some(x) if x.checksum != *migration.checksum => {
+                        MigrationState::ChecksumMismatch
+                    }
+                    Some(_) => MigrationState::Applied,
+                },
+                installed_on: applied_migration.map(|x| x.installed_on),
+            }
+        })
+        .collect();
+
+    statuses.extend(
+        applied
+            .into_iter()
+            .filter(|x| {
+                MIGRATOR
+                    .iter()
+                    .all(|migration| migration.version != x.version)
+            })
+            .map(|x| MigrationStatus {
+                version: x.version,
+                description: x.description,
+                state: MigrationState::Unknown,
+                installed_on: Some(x.installed_on),
+            }),
+    );
+    statuses.sort_by_key(|x| x.version);
+
+    Ok(statuses)
+}
+
+#[derive(Default, Props)]
+pub struct MigrationsTableProps<'a> {
+    pub migrations: Option<&'a Vec<MigrationStatus>>,
+}
+
+#[component]
+pub fn MigrationsTable<'a>(props: &MigrationsTableProps<'a>) -> impl Into<AnyElement<'a>> {
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            flex_direction: FlexDirection::Column,
+            width: 100,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom: 1) {
+                Text(content: "migrations", weight: Weight::Bold)
+            }
+
+            View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
+                View(width: 20pct, justify_content: JustifyContent::Center) {
+                    Text(content: "version", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 35pct, justify_content: JustifyContent::Center) {
+                    Text(content: "description", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 20pct, justify_content: JustifyContent::Center) {
+                    Text(content: "state", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 25pct, justify_content: JustifyContent::Center) {
+                    Text(content: "installed on", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+            }
+
+            #(props.migrations.map(|migrations| migrations.iter().enumerate().map(|(i, migration)| element! {
+                View(background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) }) {
+                    View(width: 20pct, justify_content: JustifyContent::Center) {
+                        Text(content: migration.version.to_string())
+                    }
+                    View(width: 35pct, justify_content: JustifyContent::Center) {
+                        Text(content: migration.description.clone())
+                    }
+                    View(width: 20pct, justify_content: JustifyContent::Center) {
+                        Text(
+                            content: migration.state.to_string(),
+                            color: match migration.state {
+                                MigrationState::Applied => Color::Green,
+                                MigrationState::Pending => Color::Yellow,
+                                MigrationState::ChecksumMismatch | MigrationState::Unknown => Color::Red,
+                            },
+                        )
+                    }
+                    View(width: 25pct, justify_content: JustifyContent::Center) {
+                        Text(content: migration.installed_on.map(|x| x.to_string()).unwrap_or_else(|| "N/A".to_string()))
+                    }
+                }
+            })).into_iter().flatten())
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub path: PathBuf,
+    pub current: bool,
+}
+
+/// Lists the default profile and every named profile that has a database file. The current
+/// one is the one whose database would be used, so none is with `--db` pointing elsewhere.
+pub fn get_profiles(db: Option<&Path>, profile: Option<&str>) -> Result<Vec<Profile>> {
+    let data_dir = get_data_dir()?;
+    // Compared canonicalised, so a relative `--db` to a profile's file still finds it
+    let canonicalise = |path: PathBuf| std::fs::canonicalize(&path).unwrap_or(path);
+    let current = canonicalise(get_db_path(&data_dir, db, profile)?);
+
+    let mut names = vec![DEFAULT_PROFILE.to_string()];
+    let profiles_dir = data_dir.join("profiles");
+    if profiles_dir.exists() {
+        let mut named: Vec<String> = std::fs::read_dir(&profiles_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|x| x == "sqlite"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .collect();
+        named.sort();
+        names.extend(named);
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let path = get_profile_path(&data_dir, &name)?;
+            Ok(Profile {
+                current: canonicalise(path.clone()) == current,
+                path,
+                name,
+            })
+        })
+        .collect()
+}
+
+#[derive(Default, Props)]
+pub struct ProfilesTableProps<'a> {
+    pub profiles: Option<&'a Vec<Profile>>,
+}
+
+#[component]
+pub fn ProfilesTable<'a>(props: &ProfilesTableProps<'a>) -> impl Into<AnyElement<'a>> {
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            flex_direction: FlexDirection::Column,
+            width: 100,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom: 1) {
+                Text(content: "profiles", weight: Weight::Bold)
+            }
+
+            View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
+                View(width: 20pct, justify_content: JustifyContent::Center) {
+                    Text(content: "name", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 80pct, justify_content: JustifyContent::Center) {
+                    Text(content: "path", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+            }
+
+            #(props.profiles.map(|profiles| profiles.iter().enumerate().map(|(i, profile)| element! {
+                View(background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) }) {
+                    View(width: 20pct, justify_content: JustifyContent::Center) {
+                        Text(
+                            content: if profile.current { format!("* {}", profile.name) } else { profile.name.clone() },
+                            weight: if profile.current { Weight::Bold } else { Weight::Normal },
+                        )
+                    }
+                    View(width: 80pct, justify_content: JustifyContent::Center) {
+                        Text(content: profile.path.display().to_string())
+                    }
+                }
+            })).into_iter().flatten())
+        }
+    }
+}
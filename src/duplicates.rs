@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{Executor, Pool, Sqlite};
+
+use crate::error::{Error, Result};
+use crate::progress_records::{self, ProgressRecord};
+use crate::targets::{self, Target, TargetType};
+
+/// The form item names are compared in, so "Zodiac (2007)", "zodiac 2007" and
+/// "Zodiac  (2007) " all count as the same film: lower case letters and digits, with
+/// anything else between them becoming a single space.
+pub fn normalise_item_name(item_name: &str) -> String {
+    item_name
+        .split(|x: char| !x.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The earliest of `progress_records` with the same normalised item name, leaving out the
+/// record with id `except_id`, e.g. the one being renamed.
+pub fn find_duplicate<'a>(
+    progress_records: &'a [ProgressRecord],
+    item_name: &str,
+    except_id: Option<i64>,
+) -> Option<&'a ProgressRecord> {
+    let item_name = normalise_item_name(item_name);
+    progress_records.iter().find(|x| {
+        Some(x.id) != except_id
+            && x.item_name
+                .as_deref()
+                .is_some_and(|x| normalise_item_name(x) == item_name)
+    })
+}
+
+// How many edits apart normalised names can be and still look like a typo, e.g. "zodiac"
+// and "zodaic". Short names have to be closer, as "up" and "us" are different films.
+fn max_typo_distance(item_name: &str) -> usize {
+    match item_name.chars().count() {
+        0..=3 => 0,
+        4..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// The record of `progress_records` whose normalised item name is the closest to
+/// `item_name`, if it's the same or only a typo or two away, leaving out the record with
+/// id `except_id`. Of several as close, the earliest.
+pub fn find_similar<'a>(
+    progress_records: &'a [ProgressRecord],
+    item_name: &str,
+    except_id: Option<i64>,
+) -> Option<&'a ProgressRecord> {
+    let item_name = normalise_item_name(item_name);
+    let max_distance = max_typo_distance(&item_name);
+    progress_records
+        .iter()
+        .filter(|x| Some(x.id) != except_id)
+        .filter_map(|x| {
+            let distance =
+                targets::edit_distance(&normalise_item_name(x.item_name.as_deref()?), &item_name);
+            (distance <= max_distance).then_some((x, distance))
+        })
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(x, _)| x)
+}
+
+/// Only "count" targets can have duplicates, as each of their records is one thing done.
+/// Habits are left out, as they're done again and again by design.
+pub fn has_duplicates(target: &Target) -> bool {
+    matches!(target.target_type, TargetType::Count) && target.period.is_none()
+}
+
+pub async fn get_duplicate<'e, E>(
+    db: E,
+    target: &Target,
+    item_name: &Option<String>,
+    except_id: Option<i64>,
+) -> Result<Option<ProgressRecord>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let Some(item_name) = item_name.as_ref().filter(|_| has_duplicates(target)) else {
+        return Ok(None);
+    };
+    let progress_records =
+        progress_records::get_progress_records_for_target(db, &target.id).await?;
+    Ok(find_duplicate(&progress_records, item_name, except_id).cloned())
+}
+
+/// A record with the same name as `item_name`, or one a typo or two away, which might
+/// be the same thing, e.g. "Zodiac" when adding "Zodaic".
+pub async fn get_similar(
+    db: &Pool<Sqlite>,
+    target: &Target,
+    item_name: &Option<String>,
+    except_id: Option<i64>,
+) -> Result<Option<ProgressRecord>> {
+    let Some(item_name) = item_name.as_ref().filter(|_| has_duplicates(target)) else {
+        return Ok(None);
+    };
+    let progress_records =
+        progress_records::get_progress_records_for_target(db, &target.id).await?;
+    Ok(find_similar(&progress_records, item_name, except_id).cloned())
+}
+
+pub fn duplicate_message(target: &Target, duplicate: &ProgressRecord) -> String {
+    format!(
+        "\"{}\" is already logged for \"{}\" on {} (record {})",
+        duplicate.item_name.as_deref().unwrap_or_default(),
+        target.name,
+        duplicate.entry_date,
+        duplicate.id
+    )
+}
+
+/// Refuses an item name the target already has a record of, unless it's allowed, e.g. for a rewatch.
+pub async fn check_duplicate<'e, E>(
+    db: E,
+    target: &Target,
+    item_name: &Option<String>,
+    except_id: Option<i64>,
+    allow_duplicate: bool,
+) -> Result<()>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    if allow_duplicate {
+        return Ok(());
+    }
+    match get_duplicate(db, target, item_name, except_id).await? {
+        Some(duplicate) => Err(Error::Validation(format!(
+            "{}, pass --allow-duplicate to add it again, e.g. for a rewatch",
+            duplicate_message(target, &duplicate)
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Records of one target with the same normalised item name, which merge into the earliest.
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub target_id: i64,
+    pub target_name: String,
+    pub item_name: String,
+    pub kept_id: i64,
+    pub entry_date: NaiveDate,
+    /// The records merged into the kept one, which are deleted.
+    pub merged_ids: Vec<i64>,
+}
+
+/// Finds duplicates among the records of a target, or of every target that can have them. With
+/// `same_day` only records on the same day are duplicates, so rewatches are left alone.
+pub async fn find_duplicate_groups(
+    db: &Pool<Sqlite>,
+    target: Option<&Target>,
+    same_day: bool,
+) -> Result<Vec<DuplicateGroup>> {
+    let targets = match target {
+        Some(target) => vec![target.clone()],
+        None => targets::get_targets(db, None, None).await?,
+    };
+
+    let mut groups = Vec::new();
+    for target in targets.iter().filter(|x| has_duplicates(x)) {
+        // Records come ordered by entry date, so the first of each group is the earliest
+        let progress_records =
+            progress_records::get_progress_records_for_target(db, &target.id).await?;
+        let mut keys: Vec<(String, Option<NaiveDate>)> = Vec::new();
+        let mut records_by_key: HashMap<(String, Option<NaiveDate>), Vec<&ProgressRecord>> =
+            HashMap::new();
+        for progress_record in &progress_records {
+            let Some(item_name) = &progress_record.item_name else {
+                continue;
+            };
+            let key = (
+                normalise_item_name(item_name),
+                same_day.then_some(progress_record.entry_date),
+            );
+            if !records_by_key.contains_key(&key) {
+                keys.push(key.clone());
+            }
+            records_by_key.entry(key).or_default().push(progress_record);
+        }
+
+        for key in keys {
+            let [kept, merged @ ..] = records_by_key[&key].as_slice() else {
+                continue;
+            };
+            if merged.is_empty() {
+                continue;
+            }
+            groups.push(DuplicateGroup {
+                target_id: target.id,
+                target_name: target.name.clone(),
+                item_name: kept.item_name.clone().unwrap_or_default(),
+                kept_id: kept.id,
+                entry_date: kept.entry_date,
+                merged_ids: merged.iter().map(|x| x.id).collect(),
+            });
+        }
+    }
+    Ok(groups)
+}
+
+/// Merges each group into its kept record in one transaction. A kept record without a
+/// value takes the first value of the ones merged into it. Returns how many were deleted.
+pub async fn merge_duplicates(db: &Pool<Sqlite>, groups: &[DuplicateGroup]) -> Result<u64> {
+    let mut deleted = 0;
+    let mut tx = db.begin().await?;
+    for group in groups {
+        for merged_id in &group.merged_ids {
+            sqlx::query(
+                "UPDATE progress_records
+                    SET value = COALESCE(value, (SELECT value FROM progress_records WHERE id = $2))
+                    WHERE id = $1",
+            )
+            .bind(group.kept_id)
+            .bind(merged_id)
+            .execute(&mut *tx)
+            .await?;
+            deleted += sqlx::query("DELETE FROM progress_records WHERE id = $1")
+                .bind(merged_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+    }
+    tx.commit().await?;
+    Ok(deleted)
+}
+
+#[derive(Default, Props)]
+pub struct DuplicateGroupsTableProps<'a> {
+    pub groups: Option<&'a Vec<DuplicateGroup>>,
+    pub title: &'a str,
+}
+
+#[component]
+pub fn DuplicateGroupsTable<'a>(
+    props: &DuplicateGroupsTableProps<'a>,
+) -> impl Into<AnyElement<'a>> {
+    let columns: [(&str, u16); 5] = [
+        ("target", 20),
+        ("item name", 35),
+        ("kept", 10),
+        ("date", 15),
+        ("merged", 20),
+    ];
+    let rows: Vec<[String; 5]> = props
+        .groups
+        .map(|groups| {
+            groups
+                .iter()
+                .map(|x| {
+                    [
+                        x.target_name.clone(),
+                        x.item_name.clone(),
+                        x.kept_id.to_string(),
+                        x.entry_date.to_string(),
+                        x.merged_ids
+                            .iter()
+                            .map(|x| x.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ]
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            flex_direction: FlexDirection::Column,
+            width: 100,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom:1, ) {
+                Text(content: props.title, weight: Weight::Bold )
+            }
+
+            View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
+                #(columns.iter().map(|(label, width)| element! {
+                    View(width: Percent(*width as f32), justify_content: JustifyContent::Center) {
+                        Text(content: *label, weight: Weight::Bold, decoration: TextDecoration::Underline)
+                    }
+                }))
+            }
+
+            #(rows.into_iter().enumerate().map(|(i, row)| element! {
+                View(background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) }) {
+                    #(row.into_iter().zip(columns).map(|(value, (_, width))| element! {
+                        View(width: Percent(width as f32), justify_content: JustifyContent::Center) {
+                            Text(content: value)
+                        }
+                    }))
+                }
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(item_names: &[&str]) -> Vec<ProgressRecord> {
+        item_names
+            .iter()
+            .enumerate()
+            .map(|(i, item_name)| ProgressRecord {
+                id: i as i64 + 1,
+                target_id: 1,
+                entry_date: NaiveDate::from_ymd_opt(2026, 1, i as u32 + 1).unwrap(),
+                value: None,
+                item_name: Some(item_name.to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_are_compared_normalised() {
+        assert_eq!(normalise_item_name(" Zodiac  (2007) "), "zodiac 2007");
+        let progress_records = records(&["Heat", "Zodiac (2007)"]);
+        let duplicate = find_duplicate(&progress_records, "zodiac 2007", None).unwrap();
+        assert_eq!(duplicate.id, 2);
+        assert!(find_duplicate(&progress_records, "zodiac 2007", Some(2)).is_none());
+    }
+
+    #[test]
+    fn typos_are_similar_but_not_duplicates() {
+        let progress_records = records(&["Heat", "Zodiac"]);
+        assert!(find_duplicate(&progress_records, "Zodaic", None).is_none());
+        assert_eq!(
+            find_similar(&progress_records, "Zodaic", None).unwrap().id,
+            2
+        );
+        assert_eq!(
+            find_similar(&progress_records, "zodiac", None).unwrap().id,
+            2
+        );
+        assert_eq!(
+            find_similar(&progress_records, "Heats", None).unwrap().id,
+            1
+        );
+        assert!(find_similar(&progress_records, "Zodiac", Some(2)).is_none());
+    }
+
+    #[test]
+    fn short_names_have_to_be_closer() {
+        let progress_records = records(&["Up", "Alien"]);
+        assert!(find_similar(&progress_records, "Us", None).is_none());
+        assert!(find_similar(&progress_records, "Alein", None).is_none());
+        assert_eq!(find_similar(&progress_records, "Alie", None).unwrap().id, 2);
+    }
+
+    #[test]
+    fn the_closest_is_similar() {
+        let progress_records = records(&["Zodiacs", "Zodiac"]);
+        assert_eq!(
+            find_similar(&progress_records, "Zodiac", None).unwrap().id,
+            2
+        );
+    }
+}
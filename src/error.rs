@@ -0,0 +1,96 @@
+use std::fmt;
+
+use sqlx::migrate::MigrateError;
+
+use crate::habits::ParsePeriodError;
+use crate::targets::{ParseTargetStatusError, ParseTargetTypeError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    NotFound(String),
+    Validation(String),
+    Database(sqlx::Error),
+    Migration(MigrateError),
+    Io(std::io::Error),
+}
+
+impl Error {
+    /// Exit codes that scripts can check. 2 is left for clap's usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Validation(_) => 3,
+            Error::NotFound(_) => 4,
+            Error::Database(_) => 5,
+            Error::Migration(_) => 6,
+            Error::Io(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(message) => write!(f, "{}", message),
+            Error::Validation(message) => write!(f, "{}", message),
+            Error::Database(error) => write!(f, "Database error: {}", error),
+            Error::Migration(MigrateError::VersionMismatch(version)) => write!(
+                f,
+                "Migration {} has changed since it was applied to this database, see `nyr db status`",
+                version
+            ),
+            Error::Migration(MigrateError::VersionMissing(version)) => write!(
+                f,
+                "This database has migration {} applied, which this version of nyr doesn't know about. It was probably created by a newer version of nyr, please upgrade",
+                version
+            ),
+            Error::Migration(error) => write!(f, "Migration failed: {}", error),
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Error::Database(error)
+    }
+}
+
+impl From<MigrateError> for Error {
+    fn from(error: MigrateError) -> Self {
+        Error::Migration(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<ParseTargetTypeError> for Error {
+    fn from(_: ParseTargetTypeError) -> Self {
+        Error::Validation(String::from(
+            "Target type must be one of \"count\", \"value\" or \"sum\"",
+        ))
+    }
+}
+
+impl From<ParseTargetStatusError> for Error {
+    fn from(_: ParseTargetStatusError) -> Self {
+        Error::Validation(String::from(
+            "Status must be one of \"active\", \"completed\", \"abandoned\" or \"archived\"",
+        ))
+    }
+}
+
+impl From<ParsePeriodError> for Error {
+    fn from(_: ParsePeriodError) -> Self {
+        Error::Validation(String::from(
+            "Period must be one of \"daily\", \"weekly\" or \"monthly\"",
+        ))
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use iocraft::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+
+use crate::error::{Error, Result};
+use crate::progress_records::{self, ProgressRecord};
+use crate::targets::{Target, TargetType};
+use crate::years;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "period", rename_all = "lowercase")]
+pub enum Period {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsePeriodError;
+
+impl FromStr for Period {
+    type Err = ParsePeriodError;
+
+    fn from_str(input: &str) -> std::result::Result<Period, Self::Err> {
+        match input {
+            "daily" => Ok(Period::Daily),
+            "weekly" => Ok(Period::Weekly),
+            "monthly" => Ok(Period::Monthly),
+            _ => Err(ParsePeriodError),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Period::Daily => write!(f, "daily"),
+            Period::Weekly => write!(f, "weekly"),
+            Period::Monthly => write!(f, "monthly"),
+        }
+    }
+}
+
+impl Period {
+    /// The first day of the period `date` falls in. Weeks start on Monday.
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Daily => date,
+            Period::Weekly => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Period::Monthly => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// The first day of the period after the one starting on `start`.
+    pub fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Daily => start + Days::new(1),
+            Period::Weekly => start + Days::new(7),
+            Period::Monthly => start + Months::new(1),
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Period::Daily => "day",
+            Period::Weekly => "week",
+            Period::Monthly => "month",
+        }
+    }
+
+    pub fn current_label(&self) -> &'static str {
+        match self {
+            Period::Daily => "today",
+            Period::Weekly => "this week",
+            Period::Monthly => "this month",
+        }
+    }
+
+    fn count(&self, count: u32) -> String {
+        format!(
+            "{} {}{}",
+            count,
+            self.unit(),
+            if count == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// Checks a target makes sense as a habit, which starts from nothing each period.
+pub fn validate_habit(
+    target_type: &TargetType,
+    start_value: f64,
+    auto_complete: bool,
+) -> Result<()> {
+    match target_type {
+        TargetType::Value => Err(Error::Validation(String::from(
+            "Habits have to be \"count\" or \"sum\" targets",
+        ))),
+        _ if start_value != 0.0 => Err(Error::Validation(String::from(
+            "Habits start from nothing each period, so can't have a start value",
+        ))),
+        _ if auto_complete => Err(Error::Validation(String::from(
+            "Habits can't be auto completed, as they reach 100% every period",
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HabitProgress {
+    pub period: Period,
+    /// How much needs doing each period, the target's target value.
+    pub quota: f64,
+    pub current_period_start: NaiveDate,
+    pub current_period_value: f64,
+    /// Periods in a row that met the quota, up to now. The current period only counts once it's met.
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Finished periods that didn't meet the quota.
+    pub missed_periods: u32,
+}
+
+/// How much was done in each period with records, by the period's first day.
+pub fn period_totals(
+    target: &Target,
+    period: Period,
+    progress_records: &[ProgressRecord],
+) -> HashMap<NaiveDate, f64> {
+    let mut period_values: HashMap<NaiveDate, f64> = HashMap::new();
+    for progress_record in progress_records {
+        *period_values
+            .entry(period.start_of(progress_record.entry_date))
+            .or_default() += match target.target_type {
+            TargetType::Count => 1.0,
+            TargetType::Value | TargetType::Sum => progress_record.value.unwrap_or(0.0),
+        };
+    }
+    period_values
+}
+
+impl HabitProgress {
+    /// Works through every period from the first record, or the target's creation if
+    /// that's earlier, up to today or the target date. `None` for targets without a period.
+    pub fn for_target(
+        target: &Target,
+        progress_records: &[ProgressRecord],
+        today: NaiveDate,
+    ) -> Option<HabitProgress> {
+        let period = target.period?;
+        let period_values = period_totals(target, period, progress_records);
+
+        let first_date = target.first_date(progress_records);
+        let current_period_start = period.start_of(today.min(target.target_date));
+
+        let mut start = period.start_of(first_date);
+        let mut streak = 0;
+        let mut longest_streak = 0;
+        let mut missed_periods = 0;
+        while start <= current_period_start {
+            let met = period_values.get(&start).copied().unwrap_or(0.0) >= target.target_value;
+            if met {
+                streak += 1;
+            } else if start < current_period_start {
+                streak = 0;
+                missed_periods += 1;
+            }
+            longest_streak = longest_streak.max(streak);
+            start = period.next(start);
+        }
+
+        Some(HabitProgress {
+            period,
+            quota: target.target_value,
+            current_period_start,
+            current_period_value: period_values
+                .get(&current_period_start)
+                .copied()
+                .unwrap_or(0.0),
+            current_streak: streak,
+            longest_streak,
+            missed_periods,
+        })
+    }
+
+    pub fn is_on_streak(&self) -> bool {
+        self.current_streak > 0
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{}/{} {} · streak {} · longest {} · {} missed",
+            self.current_period_value,
+            self.quota,
+            self.period.current_label(),
+            self.period.count(self.current_streak),
+            self.period.count(self.longest_streak),
+            self.missed_periods
+        )
+    }
+}
+
+pub async fn get_habit_progress(
+    db: &Pool<Sqlite>,
+    target: &Target,
+) -> Result<Option<HabitProgress>> {
+    if target.period.is_none() {
+        return Ok(None);
+    }
+    let progress_records =
+        progress_records::get_progress_records_for_target(db, &target.id).await?;
+    Ok(HabitProgress::for_target(
+        target,
+        &progress_records,
+        years::today(),
+    ))
+}
+
+#[derive(Default, Props)]
+pub struct HabitDetailProps {
+    pub habit: Option<HabitProgress>,
+}
+
+#[component]
+pub fn HabitDetail(props: &HabitDetailProps) -> impl Into<AnyElement<'static>> {
+    let rows = match &props.habit {
+        Some(habit) => vec![
+            (
+                habit.period.current_label(),
+                format!("{}/{}", habit.current_period_value, habit.quota),
+            ),
+            ("current streak", habit.period.count(habit.current_streak)),
+            ("longest streak", habit.period.count(habit.longest_streak)),
+            ("missed", habit.period.count(habit.missed_periods)),
+        ],
+        None => vec![],
+    };
+
+    element! {
+        View(flex_direction: FlexDirection::Column) {
+            #(rows.into_iter().map(|(label, value)| element! {
+                View {
+                    View(width: 20) {
+                        Text(content: label, weight: Weight::Bold)
+                    }
+                    Text(content: value)
+                }
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::targets::{self, tests::target, NewTarget};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// A count habit made on `created_at`, due at the end of the next year.
+    fn habit(period: Period, quota: f64, created_at: NaiveDate) -> Target {
+        let target_date = date(created_at.year() + 1, 12, 31);
+        Target {
+            period: Some(period),
+            ..target(created_at, target_date, 0.0, quota, 0.0)
+        }
+    }
+
+    fn records(dates: &[NaiveDate]) -> Vec<ProgressRecord> {
+        dates
+            .iter()
+            .enumerate()
+            .map(|(i, entry_date)| ProgressRecord {
+                id: i as i64 + 1,
+                target_id: 1,
+                entry_date: *entry_date,
+                value: None,
+                item_name: Some(format!("run {}", i + 1)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn met_week_then_missed_week_then_current_week() {
+        // Twice a week, from the week starting Monday 28 September
+        let target = habit(Period::Weekly, 2.0, date(2026, 9, 28));
+        let mut progress_records = records(&[
+            date(2026, 9, 29),
+            date(2026, 10, 1),
+            date(2026, 10, 7),
+            date(2026, 10, 13),
+        ]);
+
+        let habit =
+            HabitProgress::for_target(&target, &progress_records, date(2026, 10, 15)).unwrap();
+        assert_eq!(habit.current_period_start, date(2026, 10, 12));
+        assert_eq!(habit.current_period_value, 1.0);
+        // The current week isn't missed until it's over
+        assert_eq!(habit.current_streak, 0);
+        assert_eq!(habit.longest_streak, 1);
+        assert_eq!(habit.missed_periods, 1);
+
+        progress_records.extend(records(&[date(2026, 10, 15)]));
+        let habit =
+            HabitProgress::for_target(&target, &progress_records, date(2026, 10, 15)).unwrap();
+        assert_eq!(habit.current_period_value, 2.0);
+        assert_eq!(habit.current_streak, 1);
+        assert_eq!(habit.missed_periods, 1);
+    }
+
+    #[test]
+    fn weeks_run_from_monday_to_sunday() {
+        let monday = date(2026, 10, 12);
+        let sunday = date(2026, 10, 18);
+        assert_eq!(Period::Weekly.start_of(monday), monday);
+        assert_eq!(Period::Weekly.start_of(sunday), monday);
+        assert_eq!(
+            Period::Weekly.start_of(date(2026, 10, 19)),
+            date(2026, 10, 19)
+        );
+        assert_eq!(Period::Weekly.next(monday), date(2026, 10, 19));
+
+        // Sunday's record is last week's, Monday's is this week's
+        let target = habit(Period::Weekly, 1.0, monday);
+        let progress_records = records(&[sunday, date(2026, 10, 19)]);
+        let habit =
+            HabitProgress::for_target(&target, &progress_records, date(2026, 10, 19)).unwrap();
+        assert_eq!(habit.current_period_start, date(2026, 10, 19));
+        assert_eq!(habit.current_period_value, 1.0);
+        assert_eq!(habit.current_streak, 2);
+        assert_eq!(habit.missed_periods, 0);
+    }
+
+    #[test]
+    fn monthly_across_the_year_boundary() {
+        assert_eq!(
+            Period::Monthly.start_of(date(2026, 12, 31)),
+            date(2026, 12, 1)
+        );
+        assert_eq!(Period::Monthly.next(date(2026, 12, 1)), date(2027, 1, 1));
+
+        let target = habit(Period::Monthly, 1.0, date(2026, 11, 10));
+        let progress_records = records(&[date(2026, 11, 20), date(2026, 12, 31), date(2027, 1, 1)]);
+        let habit =
+            HabitProgress::for_target(&target, &progress_records, date(2027, 2, 10)).unwrap();
+        assert_eq!(habit.current_period_start, date(2027, 2, 1));
+        assert_eq!(habit.current_period_value, 0.0);
+        assert_eq!(habit.current_streak, 3);
+        assert_eq!(habit.longest_streak, 3);
+        assert_eq!(habit.missed_periods, 0);
+    }
+
+    /// The current value worked out in SQL has to agree with the one worked out here, for
+    /// targets still running and ones whose target date has passed.
+    #[tokio::test]
+    async fn current_value_agrees_with_current_period_value() {
+        let db = database::open_test_db().await.unwrap();
+        let today = years::today();
+        for target_date in [today + Days::new(60), today - Days::new(10)] {
+            for period in [Period::Daily, Period::Weekly, Period::Monthly] {
+                for target_type in [TargetType::Count, TargetType::Sum] {
+                    let new_target = NewTarget {
+                        name: format!("{} {} to {}", period, target_type, target_date),
+                        target_date: Some(target_date),
+                        target_type,
+                        start_value: None,
+                        target_value: 3.0,
+                        auto_complete: false,
+                        period: Some(period),
+                        year: None,
+                        created_at: (today - Days::new(70)).and_hms_opt(0, 0, 0),
+                    };
+                    let target = targets::create_target(&db, &new_target).await.unwrap();
+                    // Every few days over the last 70, so period starts are crossed, and a
+                    // couple logged ahead for tomorrow and the day after
+                    let entry_dates = (0..70)
+                        .step_by(3)
+                        .chain([1, 11])
+                        .map(|days_ago| today - Days::new(days_ago))
+                        .chain([today + Days::new(1), today + Days::new(2)]);
+                    for (i, entry_date) in entry_dates.enumerate() {
+                        progress_records::create_progress_record(
+                            &db,
+                            &target.id,
+                            &Some(entry_date),
+                            &Some(i as f64 + 0.5),
+                            &Some(format!("run {}", i)),
+                        )
+                        .await
+                        .unwrap();
+                    }
+                }
+            }
+        }
+
+        for target in targets::get_targets(&db, None, None).await.unwrap() {
+            let progress_records =
+                progress_records::get_progress_records_for_target(&db, &target.id)
+                    .await
+                    .unwrap();
+            let habit = HabitProgress::for_target(&target, &progress_records, today).unwrap();
+            assert_eq!(
+                target.current_value, habit.current_period_value,
+                "{}",
+                target.name
+            );
+        }
+    }
+}
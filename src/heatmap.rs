@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Days, NaiveDate};
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Sqlite};
+
+use crate::error::Result;
+use crate::habits::Period;
+
+const WEEKDAY_LABEL_WIDTH: u32 = 4;
+/// Columns taken up by each week of the heatmap.
+pub const WEEK_WIDTH: u32 = 2;
+
+/// What the colour of each day shows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HeatmapMeasure {
+    /// How many records there are.
+    #[default]
+    Count,
+    /// The total of the records' values.
+    Value,
+}
+
+#[derive(Clone, Debug, FromRow, Serialize)]
+pub struct DayActivity {
+    pub entry_date: NaiveDate,
+    pub record_count: i64,
+    pub total_value: f64,
+}
+
+impl DayActivity {
+    fn measure(&self, measure: HeatmapMeasure) -> f64 {
+        match measure {
+            HeatmapMeasure::Count => self.record_count as f64,
+            HeatmapMeasure::Value => self.total_value,
+        }
+    }
+}
+
+/// Records per day between `from` and `to`, for one target or all of them. Days
+/// without records are left out.
+pub async fn get_daily_activity(
+    db: &Pool<Sqlite>,
+    target_id: Option<i64>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<DayActivity>> {
+    let days = sqlx::query_as::<_, DayActivity>(
+        "SELECT entry_date, COUNT(*) AS record_count, COALESCE(SUM(value), 0.0) AS total_value
+            FROM progress_records
+            WHERE ($1 IS NULL OR target_id = $1) AND entry_date BETWEEN $2 AND $3
+            GROUP BY entry_date
+            ORDER BY entry_date",
+    )
+    .bind(target_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+    Ok(days)
+}
+
+// Shades of green like GitHub's contribution graph, from a little activity to the most
+fn level_color(level: usize) -> Color {
+    match level {
+        0 => Color::DarkGrey,
+        1 => Color::Rgb {
+            r: 14,
+            g: 68,
+            b: 41,
+        },
+        2 => Color::Rgb {
+            r: 0,
+            g: 109,
+            b: 50,
+        },
+        3 => Color::Rgb {
+            r: 38,
+            g: 166,
+            b: 65,
+        },
+        _ => Color::Rgb {
+            r: 57,
+            g: 211,
+            b: 83,
+        },
+    }
+}
+
+#[derive(Default, Props)]
+pub struct HeatmapProps {
+    pub days: Vec<DayActivity>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub measure: HeatmapMeasure,
+    pub title: String,
+}
+
+/// A grid of days with a column for each week, shaded by how much was done on each day.
+#[component]
+pub fn Heatmap(props: &HeatmapProps) -> impl Into<AnyElement<'static>> {
+    let values: HashMap<NaiveDate, f64> = props
+        .days
+        .iter()
+        .filter(|x| x.entry_date >= props.from && x.entry_date <= props.to)
+        .map(|x| (x.entry_date, x.measure(props.measure)))
+        .collect();
+    let max_value = values.values().copied().fold(0.0, f64::max);
+    let level = |date: &NaiveDate| match values.get(date) {
+        Some(value) if *value > 0.0 && max_value > 0.0 => (value / max_value * 4.0).ceil() as usize,
+        _ => 0,
+    };
+
+    let first_monday = Period::Weekly.start_of(props.from);
+    let week_count = ((props.to - first_monday).num_days() / 7 + 1).max(0) as u64;
+    let week_starts: Vec<NaiveDate> = (0..week_count)
+        .map(|week| first_monday + Days::new(week * 7))
+        .collect();
+
+    // Month names go above the first week that starts in them, as long as there's room
+    let mut months = vec![' '; week_starts.len() * WEEK_WIDTH as usize];
+    let mut free_from = 0;
+    let mut last_month = None;
+    for (week, week_start) in week_starts.iter().enumerate() {
+        let position = week * WEEK_WIDTH as usize;
+        let first_day = (*week_start).max(props.from);
+        let is_new_month = last_month != Some(first_day.month());
+        last_month = Some(first_day.month());
+        if is_new_month && position >= free_from && position + 3 <= months.len() {
+            let name = first_day.format("%b").to_string();
+            months.splice(position..position + 3, name.chars());
+            free_from = position + 4;
+        }
+    }
+    let months: String = months.into_iter().collect();
+
+    let total = values.values().sum::<f64>();
+    let summary = match props.measure {
+        HeatmapMeasure::Count => format!("{} records on {} days", total, values.len()),
+        HeatmapMeasure::Value => format!("{} in total over {} days", total, values.len()),
+    };
+
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            padding_left: 2,
+            padding_right: 2,
+            flex_direction: FlexDirection::Column,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(justify_content: JustifyContent::Center, margin_bottom: 1) {
+                Text(content: props.title.clone(), weight: Weight::Bold)
+            }
+            View(padding_left: WEEKDAY_LABEL_WIDTH) {
+                Text(content: months, color: Color::Grey)
+            }
+            #((0..7u64).map(|weekday| element! {
+                View(key: weekday) {
+                    View(width: WEEKDAY_LABEL_WIDTH) {
+                        Text(content: match weekday { 0 => "Mon", 2 => "Wed", 4 => "Fri", _ => "" }, color: Color::Grey)
+                    }
+                    #(week_starts.iter().map(|week_start| {
+                        let date = *week_start + Days::new(weekday);
+                        element! {
+                            View(key: date.to_string(), width: WEEK_WIDTH) {
+                                Text(
+                                    content: if date < props.from || date > props.to { " " } else { "■" },
+                                    color: level_color(level(&date)),
+                                )
+                            }
+                        }
+                    }))
+                }
+            }))
+            View(margin_top: 1, justify_content: JustifyContent::SpaceBetween) {
+                Text(content: summary, color: Color::Grey)
+                View {
+                    Text(content: "less ", color: Color::Grey)
+                    #((0..5).map(|level| element! {
+                        View(key: level, width: WEEK_WIDTH) {
+                            Text(content: "■", color: level_color(level))
+                        }
+                    }))
+                    Text(content: "more", color: Color::Grey)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,869 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::duplicates;
+use crate::error::{Error, Result};
+use crate::progress_records;
+use crate::targets::{self, NewTarget, Target, TargetType};
+use crate::years;
+
+/// Which target a row is for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RowTarget {
+    /// Found before the file was read, e.g. from `--target`.
+    Id(i64),
+    /// Read from the file, and matched to a target by its exact name, so a target named
+    /// "2024" isn't mistaken for the one with that id.
+    Name(String),
+}
+
+/// A progress record read from a file, before its target is found.
+#[derive(Clone, Debug)]
+pub struct ImportRow {
+    /// Where the record came from in the file, for reporting errors.
+    pub line: u64,
+    pub target: RowTarget,
+    pub entry_date: NaiveDate,
+    pub item_name: Option<String>,
+    pub value: Option<f64>,
+    /// A rewatch or reread, which can be logged again.
+    pub allow_duplicate: bool,
+}
+
+/// Why a line of a file couldn't be imported.
+#[derive(Clone, Debug, Serialize)]
+pub struct RowError {
+    pub line: u64,
+    pub error: String,
+}
+
+/// What targets that don't exist yet are created with, when they're created at all.
+pub struct NewTargetDefaults {
+    pub target_type: TargetType,
+    pub target_value: f64,
+}
+
+pub struct ImportOptions {
+    pub create_targets: Option<NewTargetDefaults>,
+    /// Import records for "count" targets whose item names they already have records of.
+    pub allow_duplicates: bool,
+    /// Roll everything back once it's been checked.
+    pub dry_run: bool,
+}
+
+/// How many records went into each target.
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportedTarget {
+    pub target_id: i64,
+    pub name: String,
+    pub year: i32,
+    /// Whether the import created the target.
+    pub created: bool,
+    pub record_count: usize,
+}
+
+pub struct ImportReport {
+    pub targets: Vec<ImportedTarget>,
+    /// Nothing is imported when there are any.
+    pub errors: Vec<RowError>,
+}
+
+/// Which columns of a CSV file hold what. Item name and value columns that aren't given
+/// are used if the file has them, and left empty if it doesn't.
+pub struct CsvColumns {
+    /// Not needed when every record is for the same target.
+    pub target: Option<String>,
+    pub date: String,
+    pub item_name: Option<String>,
+    pub value: Option<String>,
+}
+
+pub struct CsvOptions {
+    pub columns: CsvColumns,
+    /// The id of the target used for every row instead of a target column.
+    pub target: Option<i64>,
+    /// Like "%Y-%m-%d", see https://docs.rs/chrono/latest/chrono/format/strftime/index.html
+    pub date_format: String,
+    pub delimiter: u8,
+}
+
+fn column_index(headers: &csv::StringRecord, name: &str) -> Option<usize> {
+    headers
+        .iter()
+        .position(|x| x.trim().eq_ignore_ascii_case(name))
+}
+
+fn required_column(headers: &csv::StringRecord, name: &str) -> Result<usize> {
+    column_index(headers, name)
+        .ok_or_else(|| Error::Validation(format!("The file has no \"{}\" column", name)))
+}
+
+fn optional_column(
+    headers: &csv::StringRecord,
+    name: Option<&String>,
+    default: &str,
+) -> Result<Option<usize>> {
+    match name {
+        Some(name) => required_column(headers, name).map(Some),
+        None => Ok(column_index(headers, default)),
+    }
+}
+
+pub fn parse_date(input: &str, date_format: &str) -> std::result::Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(input.trim(), date_format).map_err(|_| {
+        format!(
+            "Date must look like {}, not \"{}\"",
+            NaiveDate::from_ymd_opt(2025, 1, 31)
+                .unwrap()
+                .format(date_format),
+            input
+        )
+    })
+}
+
+pub fn parse_value(input: &str) -> std::result::Result<Option<f64>, String> {
+    match input.trim() {
+        "" => Ok(None),
+        x => x
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Value must be a number, not \"{}\"", x)),
+    }
+}
+
+fn open_csv(path: &Path, delimiter: u8) -> Result<(csv::Reader<File>, csv::StringRecord)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|x| Error::Io(x.into()))?;
+    let headers = reader.headers().map_err(|x| Error::Io(x.into()))?.clone();
+    Ok((reader, headers))
+}
+
+/// Reads every record after the header row, handing `row` its line and a way to get a
+/// trimmed field by column, which is empty for a column the file doesn't have. `row`
+/// returns `None` for records to leave out. Records that can't be read are errors.
+fn read_rows(
+    reader: &mut csv::Reader<File>,
+    mut row: impl for<'a> FnMut(
+        u64,
+        &'a dyn Fn(Option<usize>) -> &'a str,
+    ) -> Option<std::result::Result<ImportRow, String>>,
+) -> Vec<std::result::Result<ImportRow, RowError>> {
+    let mut rows = Vec::new();
+    for result in reader.records() {
+        let record = match result {
+            Ok(x) => x,
+            Err(error) => {
+                rows.push(Err(RowError {
+                    line: error.position().map_or(0, |x| x.line()),
+                    error: error.to_string(),
+                }));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |x| x.line());
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|x| record.get(x))
+                .map(str::trim)
+                .unwrap_or_default()
+        };
+        if let Some(result) = row(line, &field) {
+            rows.push(result.map_err(|error| RowError { line, error }));
+        }
+    }
+    rows
+}
+
+/// Reads the records in a CSV file with a header row. Rows that can't be read are
+/// returned as errors, so they can all be reported together.
+pub fn read_csv(
+    path: &Path,
+    options: &CsvOptions,
+) -> Result<Vec<std::result::Result<ImportRow, RowError>>> {
+    let (mut reader, headers) = open_csv(path, options.delimiter)?;
+
+    let target_column = match (&options.target, &options.columns.target) {
+        (Some(_), _) => None,
+        (None, Some(name)) => Some(required_column(&headers, name)?),
+        (None, None) => Some(required_column(&headers, "target")?),
+    };
+    let date_column = required_column(&headers, &options.columns.date)?;
+    let item_name_column =
+        optional_column(&headers, options.columns.item_name.as_ref(), "item_name")?;
+    let value_column = optional_column(&headers, options.columns.value.as_ref(), "value")?;
+
+    Ok(read_rows(&mut reader, |line, field| {
+        Some((|| {
+            let target = match options.target {
+                Some(id) => RowTarget::Id(id),
+                None => match field(target_column) {
+                    "" => return Err(String::from("Target is missing")),
+                    x => RowTarget::Name(x.to_string()),
+                },
+            };
+            Ok(ImportRow {
+                line,
+                target,
+                entry_date: parse_date(field(Some(date_column)), &options.date_format)?,
+                item_name: Some(field(item_name_column))
+                    .filter(|x| !x.is_empty())
+                    .map(String::from),
+                value: parse_value(field(value_column))?,
+                allow_duplicate: false,
+            })
+        })())
+    }))
+}
+
+/// Entries in a site's export that are left out of an import.
+#[derive(Debug, Default)]
+pub struct SkippedEntries {
+    /// Read books without a date they were read on.
+    pub undated: usize,
+    /// From years other than the target's.
+    pub other_years: usize,
+    /// Already logged for the target, or earlier in the file.
+    pub already_logged: usize,
+}
+
+// A film or book's title with the year it came out, like "Zodiac (2007)"
+fn title_with_year(title: &str, year: &str) -> String {
+    match year.trim() {
+        "" => title.trim().to_string(),
+        year => format!("{} ({})", title.trim(), year),
+    }
+}
+
+/// Reads the films in a Letterboxd `diary.csv` for the target with id `target_id`, logged on
+/// the days they were watched. `watched.csv` works too, using the days they were logged.
+pub fn read_letterboxd(
+    path: &Path,
+    target_id: i64,
+) -> Result<Vec<std::result::Result<ImportRow, RowError>>> {
+    let (mut reader, headers) = open_csv(path, b',')?;
+    let date_column = match column_index(&headers, "Watched Date") {
+        Some(x) => x,
+        None => required_column(&headers, "Date")?,
+    };
+    let name_column = required_column(&headers, "Name")?;
+    let year_column = column_index(&headers, "Year");
+    let rewatch_column = column_index(&headers, "Rewatch");
+
+    Ok(read_rows(&mut reader, |line, field| {
+        Some(
+            parse_date(field(Some(date_column)), "%Y-%m-%d").map(|entry_date| ImportRow {
+                line,
+                target: RowTarget::Id(target_id),
+                entry_date,
+                item_name: Some(title_with_year(
+                    field(Some(name_column)),
+                    field(year_column),
+                )),
+                value: None,
+                allow_duplicate: field(rewatch_column).eq_ignore_ascii_case("yes"),
+            }),
+        )
+    }))
+}
+
+/// Reads the books on the "read" shelf of a Goodreads library export for the target with
+/// id `target_id`, logged on the days they were read. Read books without a date read are
+/// counted in `skipped`.
+pub fn read_goodreads(
+    path: &Path,
+    target_id: i64,
+    skipped: &mut SkippedEntries,
+) -> Result<Vec<std::result::Result<ImportRow, RowError>>> {
+    let (mut reader, headers) = open_csv(path, b',')?;
+    let date_column = required_column(&headers, "Date Read")?;
+    let title_column = required_column(&headers, "Title")?;
+    let shelf_column = required_column(&headers, "Exclusive Shelf")?;
+    let year_columns = [
+        column_index(&headers, "Original Publication Year"),
+        column_index(&headers, "Year Published"),
+    ];
+
+    Ok(read_rows(&mut reader, |line, field| {
+        if field(Some(shelf_column)) != "read" {
+            return None;
+        }
+        if field(Some(date_column)).is_empty() {
+            skipped.undated += 1;
+            return None;
+        }
+
+        let year = year_columns
+            .iter()
+            .map(|x| field(*x))
+            .find(|x| !x.is_empty())
+            .unwrap_or_default();
+        Some(
+            parse_date(field(Some(date_column)), "%Y/%m/%d").map(|entry_date| ImportRow {
+                line,
+                target: RowTarget::Id(target_id),
+                entry_date,
+                item_name: Some(title_with_year(field(Some(title_column)), year)),
+                value: None,
+                allow_duplicate: false,
+            }),
+        )
+    }))
+}
+
+/// Films and books from a site's export are logged one record each, on a "count" target.
+pub fn validate_log_target(target: &Target) -> Result<()> {
+    match target.target_type {
+        TargetType::Count => Ok(()),
+        _ => Err(Error::Validation(format!(
+            "\"{}\" is a \"{}\" target, but films and books are logged on \"count\" targets",
+            target.name, target.target_type
+        ))),
+    }
+}
+
+/// Leaves out rows for entries the target already has a record of, so an export can be
+/// imported again as it grows. Rewatches and rereads are only left out when there's a
+/// record of them on the same day. Unless `all_years` is set, rows from years other than
+/// the target's are left out too.
+pub async fn skip_logged(
+    db: &Pool<Sqlite>,
+    target: &Target,
+    rows: Vec<std::result::Result<ImportRow, RowError>>,
+    all_years: bool,
+    skipped: &mut SkippedEntries,
+) -> Result<Vec<std::result::Result<ImportRow, RowError>>> {
+    let entry_key = |entry_date: NaiveDate, item_name: &Option<String>| {
+        (
+            entry_date,
+            duplicates::normalise_item_name(item_name.as_deref().unwrap_or_default()),
+        )
+    };
+    let mut logged: HashSet<(NaiveDate, String)> =
+        progress_records::get_progress_records_for_target(db, &target.id)
+            .await?
+            .iter()
+            .map(|x| entry_key(x.entry_date, &x.item_name))
+            .collect();
+    let mut logged_names: HashSet<String> = logged.iter().map(|(_, x)| x.clone()).collect();
+
+    let mut kept = Vec::new();
+    for row in rows {
+        if let Ok(row) = &row {
+            if !all_years && row.entry_date.year() != target.year {
+                skipped.other_years += 1;
+                continue;
+            }
+            let key = entry_key(row.entry_date, &row.item_name);
+            if logged.contains(&key) || (!row.allow_duplicate && logged_names.contains(&key.1)) {
+                skipped.already_logged += 1;
+                continue;
+            }
+            logged_names.insert(key.1.clone());
+            logged.insert(key);
+        }
+        kept.push(row);
+    }
+    Ok(kept)
+}
+
+// Targets are matched by id or exact name. Of several with the same name, the one for the
+// record's year is picked, then a new one for that year if targets are being created,
+// then whichever `years::pick_year` picks.
+fn find_target<'a>(targets: &'a [Target], row: &ImportRow, creating: bool) -> Option<&'a Target> {
+    let name = match &row.target {
+        RowTarget::Id(id) => return targets.iter().find(|x| x.id == *id),
+        RowTarget::Name(name) => name,
+    };
+    let matches: Vec<&Target> = targets
+        .iter()
+        .filter(|x| x.name.eq_ignore_ascii_case(name))
+        .collect();
+    match matches.iter().find(|x| x.year == row.entry_date.year()) {
+        Some(target) => Some(target),
+        None if creating => None,
+        None => years::pick_year(&matches),
+    }
+}
+
+/// Adds the rows' records in one transaction, which is rolled back if any row is invalid
+/// or for a dry run.
+pub async fn import_rows(
+    db: &Pool<Sqlite>,
+    rows: Vec<std::result::Result<ImportRow, RowError>>,
+    options: &ImportOptions,
+) -> Result<ImportReport> {
+    let mut targets = targets::get_targets(db, None, None).await?;
+    let existing_target_count = targets.len();
+    let mut record_counts: HashMap<i64, usize> = HashMap::new();
+    // The normalised item names of targets that can have duplicates, once they've had a row
+    let mut item_names: HashMap<i64, HashSet<String>> = HashMap::new();
+    let mut errors = Vec::new();
+
+    let mut tx = db.begin().await?;
+    for row in rows {
+        let row = match row {
+            Ok(x) => x,
+            Err(x) => {
+                errors.push(x);
+                continue;
+            }
+        };
+
+        let target = match find_target(&targets, &row, options.create_targets.is_some()) {
+            Some(target) => target,
+            None => match (&options.create_targets, &row.target) {
+                (Some(defaults), RowTarget::Name(name)) => {
+                    let new_target = NewTarget {
+                        name: name.clone(),
+                        target_date: None,
+                        target_type: defaults.target_type.clone(),
+                        start_value: None,
+                        target_value: defaults.target_value,
+                        auto_complete: false,
+                        period: None,
+                        year: Some(row.entry_date.year()),
+                        // Backfilled years are measured from their start, like rolled over ones
+                        created_at: years::first_day_of_year(row.entry_date.year())?
+                            .and_hms_opt(0, 0, 0),
+                    };
+                    targets.push(targets::create_target(&mut *tx, &new_target).await?);
+                    targets.last().unwrap()
+                }
+                (_, target) => {
+                    errors.push(RowError {
+                        line: row.line,
+                        error: match target {
+                            RowTarget::Id(id) => format!("No target with id {}", id),
+                            RowTarget::Name(name) => format!("No target named \"{}\"", name),
+                        },
+                    });
+                    continue;
+                }
+            },
+        };
+
+        if let Err(error) = progress_records::validate_progress_record(
+            &target.target_type,
+            &row.item_name,
+            &row.value,
+        ) {
+            errors.push(RowError {
+                line: row.line,
+                error: format!("{} (\"{}\")", error, target.name),
+            });
+            continue;
+        }
+        if let Some(item_name) = row
+            .item_name
+            .as_ref()
+            .filter(|_| duplicates::has_duplicates(target))
+        {
+            let target_item_names = match item_names.entry(target.id) {
+                Entry::Occupied(x) => x.into_mut(),
+                Entry::Vacant(x) => x.insert(
+                    progress_records::get_progress_records_for_target(&mut *tx, &target.id)
+                        .await?
+                        .iter()
+                        .filter_map(|x| x.item_name.as_deref())
+                        .map(duplicates::normalise_item_name)
+                        .collect(),
+                ),
+            };
+            let is_new = target_item_names.insert(duplicates::normalise_item_name(item_name));
+            if !is_new && !options.allow_duplicates && !row.allow_duplicate {
+                errors.push(RowError {
+                    line: row.line,
+                    error: format!(
+                        "\"{}\" is already logged for \"{}\", pass --allow-duplicates to import it anyway",
+                        item_name, target.name
+                    ),
+                });
+                continue;
+            }
+        }
+        progress_records::create_progress_record(
+            &mut *tx,
+            &target.id,
+            &Some(row.entry_date),
+            &row.value,
+            &row.item_name,
+        )
+        .await?;
+        *record_counts.entry(target.id).or_default() += 1;
+    }
+
+    if options.dry_run || !errors.is_empty() {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    let imported_targets = targets
+        .iter()
+        .enumerate()
+        .filter_map(|(i, target)| {
+            record_counts.get(&target.id).map(|count| ImportedTarget {
+                target_id: target.id,
+                name: target.name.clone(),
+                year: target.year,
+                created: i >= existing_target_count,
+                record_count: *count,
+            })
+        })
+        .collect();
+    Ok(ImportReport {
+        targets: imported_targets,
+        errors,
+    })
+}
+
+#[derive(Default, Props)]
+pub struct ImportedTargetsTableProps<'a> {
+    pub targets: Option<&'a Vec<ImportedTarget>>,
+    pub title: &'a str,
+}
+
+#[component]
+pub fn ImportedTargetsTable<'a>(
+    props: &ImportedTargetsTableProps<'a>,
+) -> impl Into<AnyElement<'a>> {
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            flex_direction: FlexDirection::Column,
+            width: 80,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom:1, ) {
+                Text(content: props.title, weight: Weight::Bold )
+            }
+
+            View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
+                View(width: 10pct, justify_content: JustifyContent::Center) {
+                    Text(content: "id", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 40pct, justify_content: JustifyContent::Center) {
+                    Text(content: "target", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 15pct, justify_content: JustifyContent::Center) {
+                    Text(content: "year", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 15pct, justify_content: JustifyContent::Center) {
+                    Text(content: "new", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 20pct, justify_content: JustifyContent::Center) {
+                    Text(content: "records", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+            }
+
+            #(props.targets.map(|targets| targets.iter().enumerate().map(|(i, target)| element! {
+                View(background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) }) {
+                    View(width: 10pct, justify_content: JustifyContent::Center) {
+                        Text(content: target.target_id.to_string())
+                    }
+                    View(width: 40pct, justify_content: JustifyContent::Center) {
+                        Text(content: target.name.clone())
+                    }
+                    View(width: 15pct, justify_content: JustifyContent::Center) {
+                        Text(content: target.year.to_string())
+                    }
+                    View(width: 15pct, justify_content: JustifyContent::Center) {
+                        Text(content: if target.created { "yes" } else { "no" })
+                    }
+                    View(width: 20pct, justify_content: JustifyContent::Center) {
+                        Text(content: target.record_count.to_string())
+                    }
+                }
+            })).into_iter().flatten())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file of its own in the temporary directory, deleted once the test is done with it.
+    struct TempFile(std::path::PathBuf);
+
+    impl std::ops::Deref for TempFile {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn write_file(name: &str, contents: &str) -> TempFile {
+        let path = std::env::temp_dir().join(format!("nyr-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn csv_options(columns: CsvColumns, target: Option<i64>) -> CsvOptions {
+        CsvOptions {
+            columns,
+            target,
+            date_format: String::from("%Y-%m-%d"),
+            delimiter: b',',
+        }
+    }
+
+    fn default_columns() -> CsvColumns {
+        CsvColumns {
+            target: None,
+            date: String::from("date"),
+            item_name: None,
+            value: None,
+        }
+    }
+
+    #[test]
+    fn csv_columns_found_by_name() {
+        let path = write_file(
+            "columns.csv",
+            " Target ,DATE,value,item_name\nfilms,2026-01-02,,Heat\n2024,2026-01-03,5.5,\n",
+        );
+        let rows = read_csv(&path, &csv_options(default_columns(), None)).unwrap();
+        let rows: Vec<ImportRow> = rows.into_iter().map(std::result::Result::unwrap).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].target, RowTarget::Name(String::from("films")));
+        assert_eq!(rows[0].entry_date, date(2026, 1, 2));
+        assert_eq!(rows[0].item_name.as_deref(), Some("Heat"));
+        assert_eq!(rows[0].value, None);
+        // A number in the target column is a name, not an id
+        assert_eq!(rows[1].target, RowTarget::Name(String::from("2024")));
+        assert_eq!(rows[1].item_name, None);
+        assert_eq!(rows[1].value, Some(5.5));
+    }
+
+    #[test]
+    fn csv_columns_given_with_a_target_for_every_row() {
+        let path = write_file(
+            "mapped.csv",
+            "Film;Watched;Rating\nZodiac (2007);31/01/2026;4\n",
+        );
+        let columns = CsvColumns {
+            target: None,
+            date: String::from("watched"),
+            item_name: Some(String::from("Film")),
+            value: Some(String::from("Rating")),
+        };
+        let options = CsvOptions {
+            date_format: String::from("%d/%m/%Y"),
+            delimiter: b';',
+            ..csv_options(columns, Some(3))
+        };
+        let rows = read_csv(&path, &options).unwrap();
+        let row = rows[0].as_ref().unwrap();
+        assert_eq!(row.target, RowTarget::Id(3));
+        assert_eq!(row.entry_date, date(2026, 1, 31));
+        assert_eq!(row.item_name.as_deref(), Some("Zodiac (2007)"));
+        assert_eq!(row.value, Some(4.0));
+    }
+
+    #[test]
+    fn csv_missing_columns_are_refused() {
+        let path = write_file("no-date.csv", "target,when\nfilms,2026-01-02\n");
+        let result = read_csv(&path, &csv_options(default_columns(), None));
+        assert!(
+            matches!(result, Err(Error::Validation(x)) if x == "The file has no \"date\" column")
+        );
+
+        let columns = CsvColumns {
+            value: Some(String::from("km")),
+            ..default_columns()
+        };
+        let path = write_file("no-km.csv", "target,date,value\nrunning,2026-01-02,5\n");
+        let result = read_csv(&path, &csv_options(columns, None));
+        assert!(
+            matches!(result, Err(Error::Validation(x)) if x == "The file has no \"km\" column")
+        );
+    }
+
+    #[test]
+    fn csv_malformed_rows_are_errors() {
+        let path = write_file(
+            "malformed.csv",
+            "target,date,value\n,2026-01-02,1\nrunning,02/01/2026,1\nrunning,2026-01-02,far\nrunning,2026-01-02,5\n",
+        );
+        let rows = read_csv(&path, &csv_options(default_columns(), None)).unwrap();
+        let errors: Vec<(u64, String)> = rows
+            .iter()
+            .filter_map(|x| x.as_ref().err())
+            .map(|x| (x.line, x.error.clone()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (2, String::from("Target is missing")),
+                (
+                    3,
+                    String::from("Date must look like 2025-01-31, not \"02/01/2026\"")
+                ),
+                (4, String::from("Value must be a number, not \"far\"")),
+            ]
+        );
+        assert_eq!(rows[3].as_ref().unwrap().line, 5);
+    }
+
+    fn row(
+        target_id: i64,
+        entry_date: NaiveDate,
+        item_name: &str,
+        allow_duplicate: bool,
+    ) -> ImportRow {
+        ImportRow {
+            line: 0,
+            target: RowTarget::Id(target_id),
+            entry_date,
+            item_name: Some(String::from(item_name)),
+            value: None,
+            allow_duplicate,
+        }
+    }
+
+    #[test]
+    fn letterboxd_rewatches_can_be_logged_again() {
+        let path = write_file(
+            "diary.csv",
+            "Date,Name,Year,Letterboxd URI,Rating,Rewatch,Tags,Watched Date
+2026-01-03,Heat,1995,https://boxd.it/1,4,,,2026-01-02
+2026-02-10,Heat,1995,https://boxd.it/2,5,Yes,,2026-02-09
+2026-02-11,Primer,,https://boxd.it/3,,,,not a date
+",
+        );
+        let rows = read_letterboxd(&path, 7).unwrap();
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!(first.target, RowTarget::Id(7));
+        // The day it was watched, not logged
+        assert_eq!(first.entry_date, date(2026, 1, 2));
+        assert_eq!(first.item_name.as_deref(), Some("Heat (1995)"));
+        assert!(!first.allow_duplicate);
+        let rewatch = rows[1].as_ref().unwrap();
+        assert_eq!(rewatch.entry_date, date(2026, 2, 9));
+        assert!(rewatch.allow_duplicate);
+        assert_eq!(rows[2].as_ref().unwrap_err().line, 4);
+
+        // watched.csv has no watched date, so the day it was logged is used
+        let path = write_file(
+            "watched.csv",
+            "Date,Name,Year,Letterboxd URI\n2026-03-01,Zodiac,2007,https://boxd.it/4\n",
+        );
+        let rows = read_letterboxd(&path, 7).unwrap();
+        let row = rows[0].as_ref().unwrap();
+        assert_eq!(row.entry_date, date(2026, 3, 1));
+        assert_eq!(row.item_name.as_deref(), Some("Zodiac (2007)"));
+    }
+
+    #[test]
+    fn goodreads_only_dated_read_books() {
+        let path = write_file(
+            "goodreads.csv",
+            "Title,Exclusive Shelf,Date Read,Year Published,Original Publication Year
+Dune,read,2026/03/01,2005,1965
+Emma,to-read,,2003,1815
+Ulysses,read,,1990,1922
+The Hobbit,read,2026/04/02,1937,
+Middlemarch,currently-reading,2026/05/01,1994,1871
+",
+        );
+        let mut skipped = SkippedEntries::default();
+        let rows = read_goodreads(&path, 7, &mut skipped).unwrap();
+        let item_names: Vec<&str> = rows
+            .iter()
+            .map(|x| x.as_ref().unwrap().item_name.as_deref().unwrap())
+            .collect();
+        // Named by the year they first came out, or the edition's year without it
+        assert_eq!(item_names, ["Dune (1965)", "The Hobbit (1937)"]);
+        assert_eq!(rows[0].as_ref().unwrap().entry_date, date(2026, 3, 1));
+        assert_eq!(skipped.undated, 1);
+    }
+
+    #[tokio::test]
+    async fn skip_logged_leaves_out_what_the_target_has() {
+        let db = crate::database::open_test_db().await.unwrap();
+        let new_target = NewTarget {
+            name: String::from("films"),
+            target_date: Some(date(2026, 12, 31)),
+            target_type: TargetType::Count,
+            start_value: None,
+            target_value: 50.0,
+            auto_complete: false,
+            period: None,
+            year: None,
+            created_at: None,
+        };
+        let target = targets::create_target(&db, &new_target).await.unwrap();
+        progress_records::create_progress_record(
+            &db,
+            &target.id,
+            &Some(date(2026, 1, 2)),
+            &None,
+            &Some(String::from("Heat (1995)")),
+        )
+        .await
+        .unwrap();
+
+        let rows = vec![
+            // Logged already, even if it's written differently
+            Ok(row(target.id, date(2026, 2, 1), "heat 1995", false)),
+            // A rewatch on another day is kept, but not one on the day it's logged for
+            Ok(row(target.id, date(2026, 2, 9), "Heat (1995)", true)),
+            Ok(row(target.id, date(2026, 1, 2), "Heat (1995)", true)),
+            // Only the first time it's in the file
+            Ok(row(target.id, date(2026, 3, 1), "Zodiac (2007)", false)),
+            Ok(row(target.id, date(2026, 3, 5), "Zodiac (2007)", false)),
+            Ok(row(target.id, date(2025, 12, 31), "Alien (1979)", false)),
+            Err(RowError {
+                line: 9,
+                error: String::from("Date must look like 2025-01-31, not \"\""),
+            }),
+        ];
+        let mut skipped = SkippedEntries::default();
+        let kept = skip_logged(&db, &target, rows.clone(), false, &mut skipped)
+            .await
+            .unwrap();
+        let kept_dates: Vec<Option<NaiveDate>> = kept
+            .iter()
+            .map(|x| x.as_ref().ok().map(|x| x.entry_date))
+            .collect();
+        assert_eq!(
+            kept_dates,
+            [Some(date(2026, 2, 9)), Some(date(2026, 3, 1)), None]
+        );
+        assert_eq!(skipped.already_logged, 3);
+        assert_eq!(skipped.other_years, 1);
+
+        // Every year's films with `--all-years`
+        let mut skipped = SkippedEntries::default();
+        let kept = skip_logged(&db, &target, rows, true, &mut skipped)
+            .await
+            .unwrap();
+        assert_eq!(kept.len(), 4);
+        assert_eq!(skipped.other_years, 0);
+    }
+}
@@ -1,78 +1,33 @@
 mod app;
+mod backup;
+mod chart;
+mod database;
+mod duplicates;
+mod error;
+mod habits;
+mod heatmap;
+mod import;
+mod output;
+mod pace;
 mod progress_bar;
 mod progress_records;
+mod search;
+mod stats;
 mod targets;
+mod years;
 
+use std::io::{IsTerminal, Write};
 use std::str::FromStr;
 
-use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use iocraft::prelude::*;
-use sqlx::{
-    migrate::{MigrateDatabase, Migrator},
-    Sqlite, SqlitePool,
-};
-
-use directories::ProjectDirs;
-use include_dir::{include_dir, Dir};
+use sqlx::SqlitePool;
 use std::path::PathBuf;
 
-static MIGRATIONS_DIR: Dir = include_dir!("./migrations");
-
-fn get_db_path() -> Result<(String, PathBuf), Box<dyn std::error::Error>> {
-    let proj_dirs =
-        ProjectDirs::from("", "", "nyr").ok_or("Failed to determine project directories")?;
-
-    let data_dir = proj_dirs.data_dir();
-    std::fs::create_dir_all(data_dir)?;
-
-    let db_path = data_dir.join("database.sqlite");
-
-    let db_url = format!("sqlite:{}", db_path.display());
-
-    let migrations_path = data_dir.join("migrations");
-    if !migrations_path.exists() {
-        std::fs::create_dir_all(&migrations_path)?;
-        // Extract bundled migrations
-        for entry in MIGRATIONS_DIR.files() {
-            std::fs::write(
-                migrations_path.join(entry.path().file_name().unwrap()),
-                entry.contents(),
-            )?;
-        }
-    }
-
-    Ok((db_url, migrations_path))
-}
-
-async fn ensure_db_and_tables_exist() -> Result<SqlitePool, Box<dyn std::error::Error>> {
-    let (db_url, migrations_path) = get_db_path()?;
-
-    if !Sqlite::database_exists(&db_url).await.unwrap_or(false) {
-        log::debug!("Creating database {}", db_url);
-        Sqlite::create_database(&db_url).await.map_err(|error| {
-            log::error!("Failed to create database: {}", error);
-            error
-        })?;
-        log::debug!("Create db success");
-    } else {
-        log::debug!("Database already exists");
-    }
-
-    let db = SqlitePool::connect(&db_url).await.map_err(|error| {
-        log::error!("Failed to connect to database: {}", error);
-        error
-    })?;
-
-    match Migrator::new(migrations_path).await?.run(&db).await {
-        Ok(_) => log::debug!("Migration successful"),
-        Err(error) => {
-            log::error!("Migration failed: {}", error);
-            return Err(error.into());
-        }
-    }
-
-    Ok(db)
-}
+use error::{Error, Result};
+use output::OutputFormat;
 
 #[derive(Parser)]
 #[command(name = "progress")]
@@ -82,6 +37,26 @@ async fn ensure_db_and_tables_exist() -> Result<SqlitePool, Box<dyn std::error::
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    #[arg(long)]
+    /// Show completed, abandoned and archived targets on the dashboard too.
+    all: bool,
+
+    #[arg(short, long)]
+    /// (Optional) The year whose targets the dashboard shows. Defaults to this year.
+    year: Option<i32>,
+
+    #[arg(short, long, global = true, value_enum)]
+    /// (Optional) How to print results. Defaults to "table" in a terminal and "tsv" otherwise.
+    output: Option<OutputFormat>,
+
+    #[arg(long, global = true, env = "NYR_DB")]
+    /// (Optional) The database file to use, unless a profile is given.
+    db: Option<PathBuf>,
+
+    #[arg(short, long, global = true, env = "NYR_PROFILE")]
+    /// (Optional) A named profile, e.g. "work", which has its own database. Defaults to "default".
+    profile: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -94,45 +69,409 @@ enum Commands {
         #[command(subcommand)]
         action: RecordCommands,
     },
+    /// List the available profiles.
+    Profiles,
+    Db {
+        #[command(subcommand)]
+        action: DbCommands,
+    },
+    /// Quickly add a progress record, e.g. `nyr add films "Zodiac (2007)"` or `nyr add running 5.2`.
+    Add {
+        /// The id or name of the target. A unique prefix of the name works too.
+        target: String,
+
+        /// The name of the record for "count" targets, or its value for "value" and "sum" targets.
+        entry: String,
+
+        #[arg(short, long)]
+        /// (Optional) When the record was done. Defaults to today.
+        entry_date: Option<chrono::NaiveDate>,
+
+        #[arg(long)]
+        /// Add it even if the target already has a record with the same name, e.g. for a rewatch.
+        allow_duplicate: bool,
+    },
+    /// Show a calendar of how much was done each day of the year, like GitHub's contribution graph.
+    Heatmap {
+        #[arg(short, long)]
+        /// (Optional) The id or name of the target. A unique prefix of the name works too. Defaults to every target.
+        target: Option<String>,
+
+        #[arg(short, long)]
+        /// (Optional) The year to show. Defaults to this year.
+        year: Option<i32>,
+
+        #[arg(short, long, value_enum)]
+        /// (Optional) Shade days by the number of records or their total value. Defaults to "value" for "value" and "sum" targets and "count" otherwise.
+        measure: Option<heatmap::HeatmapMeasure>,
+    },
+    /// Show how often records are added to each target and overall, when, and how far along the targets are.
+    Stats {
+        #[arg(short, long)]
+        /// (Optional) The id or name of the target. A unique prefix of the name works too. Defaults to every active target.
+        target: Option<String>,
+
+        #[arg(long)]
+        /// (Optional) Only count records on or after this date.
+        since: Option<chrono::NaiveDate>,
+
+        #[arg(long)]
+        /// (Optional) Only count records on or before this date.
+        until: Option<chrono::NaiveDate>,
+
+        #[arg(short, long, conflicts_with = "target")]
+        /// Include targets of every status, not just active ones.
+        all: bool,
+
+        #[arg(long, conflicts_with_all = ["all", "target"])]
+        /// (Optional) Only include targets with this status ("active", "completed", "abandoned" or "archived").
+        status: Option<String>,
+
+        #[arg(short, long, conflicts_with = "target")]
+        /// (Optional) Only include targets for this year.
+        year: Option<i32>,
+    },
+    Import {
+        #[command(subcommand)]
+        action: ImportCommands,
+    },
+    /// Print a JSON backup of every target and progress record, for `nyr import json` to restore.
+    Export,
+    /// Copy a year's active and completed targets into the next year, with fresh progress.
+    Rollover {
+        #[arg(short, long)]
+        /// (Optional) The year to copy the targets from. Defaults to this year.
+        year: Option<i32>,
+    },
+    /// Compare a year's targets with the ones of the same name from the year before.
+    Compare {
+        #[arg(short, long)]
+        /// (Optional) The year to compare with the one before. Defaults to this year.
+        year: Option<i32>,
+    },
+    /// Search the names of progress records, e.g. `nyr search dune` to see if you've already read it.
+    Search {
+        /// The words to look for. The last one matches the start of a word too.
+        query: String,
+
+        #[arg(short, long)]
+        /// (Optional) The id or name of the target to search. A unique prefix of the name works too.
+        target: Option<String>,
+
+        #[arg(short, long, conflicts_with = "target")]
+        /// (Optional) Only search records for targets in this year.
+        year: Option<i32>,
+
+        #[arg(short, long, default_value_t = 20)]
+        /// The most records to show.
+        limit: i64,
+    },
+    /// Show a chart of a target's progress over time, against the pace needed to reach it by its target date.
+    Chart {
+        /// The id or name of the target. A unique prefix of the name works too.
+        target: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Add progress records from a CSV file with a header row, e.g. to backfill years of records from a spreadsheet.
+    Csv {
+        /// The CSV file to read.
+        file: PathBuf,
+
+        #[arg(short, long)]
+        /// (Optional) The id or name of the target every record is for, instead of reading it from a column.
+        target: Option<String>,
+
+        #[arg(long, conflicts_with = "target")]
+        /// (Optional) The column with each record's target name. Defaults to "target".
+        target_column: Option<String>,
+
+        #[arg(long, default_value = "date")]
+        /// The column with the date each record was done.
+        date_column: String,
+
+        #[arg(long)]
+        /// (Optional) The column with each record's name. Defaults to "item_name" if there is one.
+        item_column: Option<String>,
+
+        #[arg(long)]
+        /// (Optional) The column with each record's value. Defaults to "value" if there is one.
+        value_column: Option<String>,
+
+        #[arg(long, default_value = "%Y-%m-%d")]
+        /// How dates are written, e.g. "%d/%m/%Y" for 31/01/2025.
+        date_format: String,
+
+        #[arg(long, default_value_t = ',')]
+        /// The character between fields, e.g. ';' or a tab.
+        delimiter: char,
+
+        #[arg(long)]
+        /// Create targets that don't exist yet, one for each year that has records.
+        create_targets: bool,
+
+        #[arg(long, requires = "create_targets")]
+        /// (Optional) The type ("count", "value" or "sum") of targets that are created. Defaults to "count".
+        target_type: Option<String>,
+
+        #[arg(long, requires = "create_targets")]
+        /// The target value of targets that are created. Needed with --create-targets.
+        target_value: Option<f64>,
+
+        #[arg(long)]
+        /// Import records for "count" targets with the same name as ones they already have, e.g. rewatches.
+        allow_duplicates: bool,
+
+        #[arg(long)]
+        /// Check the file and show what would be imported, without importing anything.
+        dry_run: bool,
+    },
+    /// Log the films in a Letterboxd diary.csv export on a "count" target, skipping ones already logged.
+    Letterboxd {
+        /// The diary.csv file from Letterboxd's export.
+        file: PathBuf,
+
+        #[arg(short, long)]
+        /// The id or name of the target to log the films on.
+        target: String,
+
+        #[arg(long)]
+        /// Import films watched in any year, not just the target's.
+        all_years: bool,
+
+        #[arg(long)]
+        /// Check the file and show what would be imported, without importing anything.
+        dry_run: bool,
+    },
+    /// Log the books on the "read" shelf of a Goodreads library export on a "count" target, skipping ones already logged.
+    Goodreads {
+        /// The CSV file from Goodreads' library export.
+        file: PathBuf,
+
+        #[arg(short, long)]
+        /// The id or name of the target to log the books on.
+        target: String,
+
+        #[arg(long)]
+        /// Import books read in any year, not just the target's.
+        all_years: bool,
+
+        #[arg(long)]
+        /// Check the file and show what would be imported, without importing anything.
+        dry_run: bool,
+    },
+    /// Restore a backup made by `nyr export`. Targets with the same name and year as ones already there are merged, skipping records they already have.
+    Json {
+        /// The backup file to read.
+        file: PathBuf,
+
+        #[arg(long)]
+        /// Delete every target and progress record first, instead of merging.
+        replace: bool,
+
+        #[arg(long)]
+        /// Check the backup and show what would be restored, without restoring anything.
+        dry_run: bool,
+
+        #[arg(short, long)]
+        /// Don't ask before replacing everything.
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Show which migrations have been applied to the database and which are pending.
+    Status,
 }
 
 #[derive(Subcommand)]
 enum TargetCommands {
-    List,
+    List {
+        #[arg(short, long)]
+        /// Show targets of every status, not just active ones.
+        all: bool,
+
+        #[arg(long, conflicts_with = "all")]
+        /// (Optional) Only show targets with this status ("active", "completed", "abandoned" or "archived").
+        status: Option<String>,
+
+        #[arg(short, long)]
+        /// (Optional) Only show targets for this year.
+        year: Option<i32>,
+    },
+    Show {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target. A unique prefix of the name works too.
+        id: String,
+    },
     Create {
         #[arg(short, long)]
         /// The target you're trying to achieve.
         name: String,
 
         #[arg(long)]
-        /// (Optional) The type of target ("count" or "value") you're trying to achieve. Defaults to "count".
+        /// (Optional) The type of target ("count", "value" or "sum") you're trying to achieve. Defaults to "count".
         target_type: Option<String>,
 
         #[arg(short = 'd', long)]
-        /// (Optional) When you'd like to achieve the goal by. Defaults to end of this year.
+        /// (Optional) When you'd like to achieve the goal by. Defaults to the end of the target's year.
         target_date: Option<chrono::NaiveDate>,
 
+        #[arg(short, long)]
+        /// (Optional) The year whose set of goals the target belongs to. Defaults to the year of the target date, or this year.
+        year: Option<i32>,
+
         #[arg(short, long)]
         /// (Optional) The starting value of your target. Defaults to 0.
         start_value: Option<f64>,
 
         #[arg(short, long)]
-        /// The target value you're trying to achieve.
+        /// The target value you're trying to achieve. For habits, how much to do each period.
         target_value: f64,
+
+        #[arg(long)]
+        /// Mark the target completed automatically once it reaches 100%.
+        auto_complete: bool,
+
+        #[arg(long)]
+        /// (Optional) Make this a habit that repeats "daily", "weekly" or "monthly", e.g. `--period weekly --target-value 3` for 3 times a week.
+        period: Option<String>,
+    },
+    Update {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target. A unique prefix of the name works too.
+        id: String,
+
+        #[arg(short, long)]
+        /// (Optional) The new name of the target.
+        name: Option<String>,
+
+        #[arg(long)]
+        /// (Optional) The new type of target ("count", "value" or "sum").
+        target_type: Option<String>,
+
+        #[arg(short = 'd', long)]
+        /// (Optional) The new date you'd like to achieve the goal by.
+        target_date: Option<chrono::NaiveDate>,
+
+        #[arg(short, long)]
+        /// (Optional) The new starting value of your target.
+        start_value: Option<f64>,
+
+        #[arg(short, long)]
+        /// (Optional) The new target value you're trying to achieve.
+        target_value: Option<f64>,
+
+        #[arg(long)]
+        /// (Optional) Whether to mark the target completed automatically once it reaches 100%.
+        auto_complete: Option<bool>,
+
+        #[arg(long)]
+        /// (Optional) Make this a habit that repeats "daily", "weekly" or "monthly", or change how often it repeats.
+        period: Option<String>,
+
+        #[arg(short, long)]
+        /// (Optional) The new year whose set of goals the target belongs to.
+        year: Option<i32>,
+    },
+    /// Mark a target as completed.
+    Complete {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target. A unique prefix of the name works too.
+        id: String,
+    },
+    /// Mark a target as abandoned.
+    Abandon {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target. A unique prefix of the name works too.
+        id: String,
+    },
+    /// Mark a completed, abandoned or archived target as active again.
+    Reactivate {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target. A unique prefix of the name works too.
+        id: String,
     },
+    /// Archive a target so it's hidden without being deleted.
+    Archive {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target. A unique prefix of the name works too.
+        id: String,
+    },
+    /// Delete a target. Refuses if it has progress records unless told what to do with them.
     Delete {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target. A unique prefix of the name works too.
+        id: String,
+
+        #[arg(long)]
+        /// Delete the target's progress records too.
+        cascade: bool,
+
+        #[arg(long, conflicts_with = "cascade")]
+        /// (Optional) The id or name of a target to move the progress records to.
+        reassign_to: Option<String>,
+
         #[arg(short, long)]
-        id: i64,
+        /// Don't ask for confirmation.
+        yes: bool,
     },
 }
 
 #[derive(Subcommand)]
 enum RecordCommands {
-    List,
-    Create {
+    List {
+        #[arg(short, long, visible_alias = "target", alias = "target-name")]
+        /// (Optional) Only show records for the target with this id or name.
+        target_id: Option<String>,
+
+        #[arg(long)]
+        /// (Optional) Only show records on or after this date.
+        since: Option<chrono::NaiveDate>,
+
+        #[arg(long)]
+        /// (Optional) Only show records on or before this date.
+        until: Option<chrono::NaiveDate>,
+
+        #[arg(short, long)]
+        /// (Optional) Only show records for targets in this year.
+        year: Option<i32>,
+
+        #[arg(short, long)]
+        /// (Optional) Only show records whose name contains this text.
+        item_name: Option<String>,
+
+        #[arg(long)]
+        /// (Optional) Only show records with at least this value.
+        min_value: Option<f64>,
+
+        #[arg(long)]
+        /// (Optional) Only show records with at most this value.
+        max_value: Option<f64>,
+
+        #[arg(short, long, value_enum, default_value_t)]
+        /// What to sort the records by.
+        sort: progress_records::ProgressRecordSort,
+
+        #[arg(long)]
+        /// Sort in descending order.
+        desc: bool,
+
         #[arg(short, long)]
-        /// The id of the target that this record is for.
-        target_id: i64,
+        /// (Optional) The most records to show.
+        limit: Option<i64>,
+
+        #[arg(long)]
+        /// (Optional) How many records to skip before showing any.
+        offset: Option<i64>,
+    },
+    Create {
+        #[arg(short, long, visible_alias = "target")]
+        /// The id or name of the target that this record is for.
+        target_id: String,
         #[arg(short, long)]
         /// (Optional) When the record was done. Defaults to today.
         entry_date: Option<chrono::NaiveDate>,
@@ -140,95 +479,927 @@ enum RecordCommands {
         /// (Optional for "value" targets) The name of the record.
         item_name: Option<String>,
         #[arg(short, long)]
-        /// (Optional for "count" targets) The value you want to record.
+        /// (Optional for "count" targets) The value you want to record, or add to the total for "sum" targets.
+        value: Option<f64>,
+        #[arg(long)]
+        /// Add it even if the target already has a record with the same name, e.g. for a rewatch.
+        allow_duplicate: bool,
+    },
+    Update {
+        #[arg(long)]
+        id: i64,
+        #[arg(short, long)]
+        /// (Optional) The new date the record was done.
+        entry_date: Option<chrono::NaiveDate>,
+        #[arg(short, long)]
+        /// (Optional) The new name of the record.
+        item_name: Option<String>,
+        #[arg(short, long)]
+        /// (Optional) The new value of the record.
         value: Option<f64>,
+        #[arg(long)]
+        /// Rename it even if the target already has another record with the new name.
+        allow_duplicate: bool,
     },
     Delete {
         #[arg(short, long)]
         id: i64,
     },
+    /// Find records of "count" targets, other than habits, with the same name, ignoring case and punctuation, and merge each lot into the earliest.
+    Dedupe {
+        #[arg(short, long)]
+        /// (Optional) The id or name of the target to look in. Defaults to every "count" target that isn't a habit.
+        target: Option<String>,
+
+        #[arg(long)]
+        /// Only merge records on the same day, leaving rewatches and rereads on other days alone.
+        same_day: bool,
+
+        #[arg(long)]
+        /// Show what would be merged, without merging anything.
+        dry_run: bool,
+
+        #[arg(short, long)]
+        /// Don't ask before merging.
+        yes: bool,
+    },
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(prompt: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::Validation(String::from(
+            "Can't ask for confirmation when not run in a terminal, pass --yes to confirm",
+        )));
+    }
+
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn print_targets(output: OutputFormat, targets: Vec<targets::Target>, title: &str) -> Result<()> {
+    match output {
+        OutputFormat::Table => {
+            element!(targets::TargetsTable(targets: &targets, title: title)).print();
+            Ok(())
+        }
+        format => {
+            let rows: Vec<_> = targets.iter().map(targets::TargetOutput::from).collect();
+            output::print_rows(format, &rows)
+        }
+    }
+}
+
+fn print_progress_records(
+    output: OutputFormat,
+    progress_records: Vec<progress_records::ProgressRecord>,
+    title: &str,
+) -> Result<()> {
+    match output {
+        OutputFormat::Table => {
+            element!(progress_records::ProgressRecordsTable(progress_records: &progress_records, title: title)).print();
+            Ok(())
+        }
+        format => output::print_rows(format, &progress_records),
+    }
+}
+
+async fn create_progress_record(
+    db: &SqlitePool,
+    output: OutputFormat,
+    target: &targets::Target,
+    entry_date: &Option<chrono::NaiveDate>,
+    value: &Option<f64>,
+    item_name: &Option<String>,
+    allow_duplicate: bool,
+) -> Result<()> {
+    progress_records::validate_progress_record(&target.target_type, item_name, value)?;
+    duplicates::check_duplicate(db, target, item_name, None, allow_duplicate).await?;
+    if !allow_duplicate {
+        print_if_similar(db, target, item_name, None).await?;
+    }
+
+    let progress_record =
+        progress_records::create_progress_record(db, &target.id, entry_date, value, item_name)
+            .await?;
+    print_progress_records(output, vec![progress_record], "progress record created")?;
+    print_if_auto_completed(db, &target.id).await
+}
+
+fn import_options(
+    create_targets: bool,
+    target_type: &Option<String>,
+    target_value: Option<f64>,
+    allow_duplicates: bool,
+    dry_run: bool,
+) -> Result<import::ImportOptions> {
+    let create_targets = match (create_targets, target_value) {
+        (false, _) => None,
+        (true, None) => {
+            return Err(Error::Validation(String::from(
+                "--target-value is needed to create targets",
+            )))
+        }
+        (true, Some(target_value)) => Some(import::NewTargetDefaults {
+            target_type: match target_type {
+                Some(x) => targets::TargetType::from_str(x)?,
+                None => targets::TargetType::Count,
+            },
+            target_value,
+        }),
+    };
+    Ok(import::ImportOptions {
+        create_targets,
+        allow_duplicates,
+        dry_run,
+    })
+}
+
+/// Imports the films or books from a site's export that the target hasn't logged yet.
+async fn import_log(
+    db: &SqlitePool,
+    output: OutputFormat,
+    target: &targets::Target,
+    rows: Vec<std::result::Result<import::ImportRow, import::RowError>>,
+    mut skipped: import::SkippedEntries,
+    all_years: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let rows = import::skip_logged(db, target, rows, all_years, &mut skipped).await?;
+    let entries = |count: usize| if count == 1 { "entry" } else { "entries" };
+    if skipped.undated > 0 {
+        eprintln!(
+            "Skipped {} read {} with no date read",
+            skipped.undated,
+            if skipped.undated == 1 {
+                "book"
+            } else {
+                "books"
+            }
+        );
+    }
+    if skipped.other_years > 0 {
+        eprintln!(
+            "Skipped {} {} from years other than {}, pass --all-years to import them too",
+            skipped.other_years,
+            entries(skipped.other_years),
+            target.year
+        );
+    }
+    if skipped.already_logged > 0 {
+        eprintln!(
+            "Skipped {} {} already logged for \"{}\"",
+            skipped.already_logged,
+            entries(skipped.already_logged),
+            target.name
+        );
+    }
+
+    // Rewatches and rereads are marked on the rows, and everything else logged was skipped
+    let options = import::ImportOptions {
+        create_targets: None,
+        allow_duplicates: false,
+        dry_run,
+    };
+    let report = import::import_rows(db, rows, &options).await?;
+    print_import_report(db, output, report, dry_run).await
+}
+
+/// Lists every row that couldn't be imported, or what was imported into each target.
+async fn print_import_report(
+    db: &SqlitePool,
+    output: OutputFormat,
+    report: import::ImportReport,
+    dry_run: bool,
+) -> Result<()> {
+    if !report.errors.is_empty() {
+        for row_error in &report.errors {
+            eprintln!("Line {}: {}", row_error.line, row_error.error);
+        }
+        return Err(Error::Validation(format!(
+            "{} {} couldn't be imported, so nothing was",
+            report.errors.len(),
+            if report.errors.len() == 1 {
+                "row"
+            } else {
+                "rows"
+            }
+        )));
+    }
+
+    let title = if dry_run {
+        "would be imported (dry run)"
+    } else {
+        "imported"
+    };
+    match output {
+        OutputFormat::Table => {
+            element!(import::ImportedTargetsTable(targets: &report.targets, title: title)).print()
+        }
+        format => output::print_rows(format, &report.targets)?,
+    }
+    if !dry_run {
+        for target in &report.targets {
+            print_if_auto_completed(db, &target.target_id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Points out a record whose name is only a typo or two away, as it might be the same thing.
+async fn print_if_similar(
+    db: &SqlitePool,
+    target: &targets::Target,
+    item_name: &Option<String>,
+    except_id: Option<i64>,
+) -> Result<()> {
+    if let Some(similar) = duplicates::get_similar(db, target, item_name, except_id).await? {
+        eprintln!(
+            "{}, check it isn't the same with a typo",
+            duplicates::duplicate_message(target, &similar)
+        );
+    }
+    Ok(())
+}
+
+async fn print_if_auto_completed(db: &SqlitePool, target_id: &i64) -> Result<()> {
+    if let Some(target) = targets::auto_complete_target(db, target_id).await? {
+        eprintln!(
+            "Target \"{}\" reached 100% and was marked completed",
+            target.name
+        );
+    }
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-    let db = ensure_db_and_tables_exist().await.unwrap();
-    let cli = Cli::parse();
+    let mut command = Cli::command();
+    let matches = command.get_matches_mut();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|x| x.exit());
+
+    // `--db` and `--profile` can't be passed together, but either beats the other's
+    // environment variable. This can't be `conflicts_with`, as clap counts those too.
+    let from_command_line = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+    match (from_command_line("db"), from_command_line("profile")) {
+        (true, true) => command
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the argument '--db <DB>' cannot be used with '--profile <PROFILE>'",
+            )
+            .exit(),
+        (true, false) => cli.profile = None,
+        (false, true) => cli.db = None,
+        (false, false) => {}
+    }
+    if let Err(error) = run(cli).await {
+        eprintln!("Error: {}", error);
+        std::process::exit(error.exit_code());
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let output = OutputFormat::resolve(cli.output);
+
+    // Listing profiles shouldn't create a database for the one that's selected
+    if let Some(Commands::Profiles) = cli.command {
+        let profiles = database::get_profiles(cli.db.as_deref(), cli.profile.as_deref())?;
+        match output {
+            OutputFormat::Table => element!(database::ProfilesTable(profiles: &profiles)).print(),
+            format => output::print_rows(format, &profiles)?,
+        }
+        return Ok(());
+    }
+
+    // Neither should checking the migrations apply them
+    if let Some(Commands::Db {
+        action: DbCommands::Status,
+    }) = cli.command
+    {
+        let migrations =
+            database::get_migration_statuses(cli.db.as_deref(), cli.profile.as_deref()).await?;
+        match output {
+            OutputFormat::Table => {
+                element!(database::MigrationsTable(migrations: &migrations)).print()
+            }
+            format => output::print_rows(format, &migrations)?,
+        }
+        return Ok(());
+    }
+
+    let db =
+        database::ensure_db_and_tables_exist(cli.db.as_deref(), cli.profile.as_deref()).await?;
     match &cli.command {
         Some(Commands::Targets { action }) => match action {
-            TargetCommands::List => {
-                let targets = targets::get_targets(&db).await;
-                element!(targets::TargetsTable(targets: &targets, title: "targets")).print();
+            TargetCommands::List { all, status, year } => {
+                let checked_status = match status {
+                    Some(x) => Some(targets::TargetStatus::from_str(x)?),
+                    None if *all => None,
+                    None => Some(targets::TargetStatus::Active),
+                };
+                let targets = targets::get_targets(&db, checked_status.as_ref(), *year).await?;
+                print_targets(output, targets, "targets")?;
+            }
+            TargetCommands::Show { id } => {
+                let target = targets::resolve_target(&db, id).await?;
+                let habit = habits::get_habit_progress(&db, &target).await?;
+                match output {
+                    OutputFormat::Table => {
+                        element!(targets::TargetDetail(target: &target, habit)).print()
+                    }
+                    format => output::print_rows(
+                        format,
+                        &[targets::TargetOutput {
+                            habit,
+                            ..targets::TargetOutput::from(&target)
+                        }],
+                    )?,
+                }
             }
             TargetCommands::Create {
                 name,
                 target_date,
+                year,
                 target_type,
                 start_value,
                 target_value,
+                auto_complete,
+                period,
             } => {
-                let checked_target_type = match target_type {
-                    Some(x) => targets::TargetType::from_str(x).unwrap(),
-                    None => targets::TargetType::Count,
+                let new_target = targets::NewTarget {
+                    name: name.clone(),
+                    target_date: *target_date,
+                    target_type: match target_type {
+                        Some(x) => targets::TargetType::from_str(x)?,
+                        None => targets::TargetType::Count,
+                    },
+                    start_value: *start_value,
+                    target_value: *target_value,
+                    auto_complete: *auto_complete,
+                    period: period
+                        .as_deref()
+                        .map(habits::Period::from_str)
+                        .transpose()?,
+                    year: *year,
+                    created_at: None,
                 };
 
-                let target_create_result = targets::create_target(
-                    &db,
-                    name,
-                    target_date,
-                    checked_target_type,
-                    start_value,
-                    target_value,
-                )
-                .await;
-                let mut targets = Vec::new();
-                targets.push(target_create_result);
-                element!(targets::TargetsTable(targets: &targets, title: "target created")).print();
+                let target_create_result = targets::create_target(&db, &new_target).await?;
+                print_targets(output, vec![target_create_result], "target created")?;
+            }
+            TargetCommands::Update {
+                id,
+                name,
+                target_type,
+                target_date,
+                start_value,
+                target_value,
+                auto_complete,
+                period,
+                year,
+            } => {
+                let target = targets::resolve_target(&db, id).await?;
+                let checked_target_type = target_type
+                    .as_ref()
+                    .map(|x| targets::TargetType::from_str(x))
+                    .transpose()?;
+
+                let changes = targets::TargetChanges {
+                    name: name.clone(),
+                    target_date: *target_date,
+                    target_type: checked_target_type,
+                    start_value: *start_value,
+                    target_value: *target_value,
+                    auto_complete: *auto_complete,
+                    period: period
+                        .as_deref()
+                        .map(habits::Period::from_str)
+                        .transpose()?,
+                    year: *year,
+                };
+                let target = targets::update_target(&db, &target.id, &changes).await?;
+                print_targets(output, vec![target], "target updated")?;
+            }
+            TargetCommands::Complete { id } => {
+                let target = targets::resolve_target(&db, id).await?;
+                let target =
+                    targets::set_target_status(&db, &target.id, targets::TargetStatus::Completed)
+                        .await?;
+                print_targets(output, vec![target], "target completed")?;
+            }
+            TargetCommands::Abandon { id } => {
+                let target = targets::resolve_target(&db, id).await?;
+                let target =
+                    targets::set_target_status(&db, &target.id, targets::TargetStatus::Abandoned)
+                        .await?;
+                print_targets(output, vec![target], "target abandoned")?;
+            }
+            TargetCommands::Reactivate { id } => {
+                let target = targets::resolve_target(&db, id).await?;
+                let target =
+                    targets::set_target_status(&db, &target.id, targets::TargetStatus::Active)
+                        .await?;
+                print_targets(output, vec![target], "target reactivated")?;
             }
-            TargetCommands::Delete { id } => {
-                targets::delete_target(&db, id).await;
+            TargetCommands::Archive { id } => {
+                let target = targets::resolve_target(&db, id).await?;
+                let target =
+                    targets::set_target_status(&db, &target.id, targets::TargetStatus::Archived)
+                        .await?;
+                print_targets(output, vec![target], "target archived")?;
+            }
+            TargetCommands::Delete {
+                id,
+                cascade,
+                reassign_to,
+                yes,
+            } => {
+                let target = targets::resolve_target(&db, id).await?;
+                let records =
+                    progress_records::get_progress_records_for_target(&db, &target.id).await?;
+
+                let (delete_mode, prompt) = match (reassign_to, cascade) {
+                    (Some(reassign_to), _) => {
+                        let new_target = targets::resolve_target(&db, reassign_to).await?;
+                        if new_target.id == target.id {
+                            return Err(Error::Validation(String::from(
+                                "Can't reassign progress records to the target being deleted",
+                            )));
+                        }
+                        for progress_record in &records {
+                            progress_records::validate_progress_record(
+                                &new_target.target_type,
+                                &progress_record.item_name,
+                                &progress_record.value,
+                            )?;
+                        }
+                        (
+                            targets::DeleteMode::Reassign(new_target.id),
+                            format!(
+                                "Delete target \"{}\" and move its {} progress records to \"{}\"?",
+                                target.name,
+                                records.len(),
+                                new_target.name
+                            ),
+                        )
+                    }
+                    (None, true) => (
+                        targets::DeleteMode::Cascade,
+                        format!(
+                            "Delete target \"{}\" and its {} progress records?",
+                            target.name,
+                            records.len()
+                        ),
+                    ),
+                    (None, false) if !records.is_empty() => {
+                        return Err(Error::Validation(format!(
+                            "Target \"{}\" has {} progress records. Use --cascade to delete them too, or --reassign-to to move them to another target",
+                            target.name,
+                            records.len()
+                        )));
+                    }
+                    (None, false) => (
+                        targets::DeleteMode::Refuse,
+                        format!("Delete target \"{}\"?", target.name),
+                    ),
+                };
+
+                if !yes && !confirm(&prompt)? {
+                    println!("Nothing deleted");
+                    return Ok(());
+                }
+
+                let affected = targets::delete_target(&db, &target.id, delete_mode).await?;
+                println!(
+                    "Target deleted, {} progress records {}",
+                    affected,
+                    if reassign_to.is_some() {
+                        "reassigned"
+                    } else {
+                        "deleted"
+                    }
+                );
             }
         },
         Some(Commands::Records { action }) => match action {
-            RecordCommands::List => {
-                let progress_records = progress_records::get_progress_records(&db).await;
-                element!(progress_records::ProgressRecordsTable(progress_records: &progress_records, title: "progress records")).print();
+            RecordCommands::List {
+                target_id,
+                since,
+                until,
+                year,
+                item_name,
+                min_value,
+                max_value,
+                sort,
+                desc,
+                limit,
+                offset,
+            } => {
+                let target_id = match target_id {
+                    Some(x) => Some(targets::resolve_target(&db, x).await?.id),
+                    None => None,
+                };
+                let filter = progress_records::ProgressRecordFilter {
+                    target_id,
+                    since: *since,
+                    until: *until,
+                    year: *year,
+                    item_name: item_name.clone(),
+                    min_value: *min_value,
+                    max_value: *max_value,
+                    sort: *sort,
+                    descending: *desc,
+                    limit: *limit,
+                    offset: *offset,
+                };
+                let progress_records = progress_records::get_progress_records(&db, &filter).await?;
+                print_progress_records(output, progress_records, "progress records")?;
             }
             RecordCommands::Create {
                 target_id,
                 entry_date,
                 item_name,
                 value,
+                allow_duplicate,
             } => {
-                let target = targets::get_target(&db, &target_id).await;
-                match target.target_type {
-                    targets::TargetType::Count => {
-                        if item_name.is_none() {
-                            panic!("Item name is required for count targets");
-                        }
+                let target = targets::resolve_target(&db, target_id).await?;
+                create_progress_record(
+                    &db,
+                    output,
+                    &target,
+                    entry_date,
+                    value,
+                    item_name,
+                    *allow_duplicate,
+                )
+                .await?;
+            }
+            RecordCommands::Update {
+                id,
+                entry_date,
+                item_name,
+                value,
+                allow_duplicate,
+            } => {
+                let progress_record = progress_records::update_progress_record(
+                    &db,
+                    id,
+                    entry_date,
+                    value,
+                    item_name,
+                    *allow_duplicate,
+                )
+                .await?;
+                let target = targets::get_target(&db, &progress_record.target_id).await?;
+                print_progress_records(output, vec![progress_record], "progress record updated")?;
+                if !allow_duplicate {
+                    print_if_similar(&db, &target, item_name, Some(*id)).await?;
+                }
+                print_if_auto_completed(&db, &target.id).await?;
+            }
+            RecordCommands::Delete { id } => {
+                progress_records::delete_progress_record(&db, id).await?;
+                println!("Record deleted");
+            }
+            RecordCommands::Dedupe {
+                target,
+                same_day,
+                dry_run,
+                yes,
+            } => {
+                let target = match target {
+                    Some(x) => Some(targets::resolve_target(&db, x).await?),
+                    None => None,
+                };
+                let groups =
+                    duplicates::find_duplicate_groups(&db, target.as_ref(), *same_day).await?;
+                if groups.is_empty() {
+                    println!("No duplicates found");
+                    return Ok(());
+                }
+
+                let title = if *dry_run {
+                    "would be merged (dry run)"
+                } else {
+                    "duplicates"
+                };
+                match output {
+                    OutputFormat::Table => {
+                        element!(duplicates::DuplicateGroupsTable(groups: &groups, title)).print()
                     }
-                    targets::TargetType::Value => {
-                        if value.is_none() {
-                            panic!("Value is required for value targets");
-                        }
+                    format => output::print_rows(format, &groups)?,
+                }
+                if *dry_run {
+                    return Ok(());
+                }
+
+                let merged_count: usize = groups.iter().map(|x| x.merged_ids.len()).sum();
+                let prompt = format!(
+                    "Merge {} duplicate {} into the earliest of each?",
+                    merged_count,
+                    if merged_count == 1 {
+                        "record"
+                    } else {
+                        "records"
                     }
+                );
+                if !yes && !confirm(&prompt)? {
+                    println!("Nothing merged");
+                    return Ok(());
+                }
+                let deleted = duplicates::merge_duplicates(&db, &groups).await?;
+                eprintln!(
+                    "{} duplicate {} merged",
+                    deleted,
+                    if deleted == 1 { "record" } else { "records" }
+                );
+            }
+        },
+        Some(Commands::Add {
+            target,
+            entry,
+            entry_date,
+            allow_duplicate,
+        }) => {
+            let target = targets::resolve_target(&db, target).await?;
+            let (item_name, value) = match target.target_type {
+                targets::TargetType::Count => (Some(entry.clone()), None),
+                targets::TargetType::Value | targets::TargetType::Sum => {
+                    let value = entry.parse::<f64>().map_err(|_| {
+                        Error::Validation(format!(
+                            "\"{}\" isn't a number, which {} targets need",
+                            entry, target.target_type
+                        ))
+                    })?;
+                    (None, Some(value))
                 }
+            };
+            create_progress_record(
+                &db,
+                output,
+                &target,
+                entry_date,
+                &value,
+                &item_name,
+                *allow_duplicate,
+            )
+            .await?;
+        }
+        Some(Commands::Heatmap {
+            target,
+            year,
+            measure,
+        }) => {
+            let target = match target {
+                Some(x) => Some(targets::resolve_target(&db, x).await?),
+                None => None,
+            };
+            let year = year.unwrap_or_else(years::this_year);
+            let from = years::first_day_of_year(year)?;
+            let to = years::last_day_of_year(year)?;
+            let measure = measure.unwrap_or(match target.as_ref().map(|x| &x.target_type) {
+                Some(targets::TargetType::Value | targets::TargetType::Sum) => {
+                    heatmap::HeatmapMeasure::Value
+                }
+                _ => heatmap::HeatmapMeasure::Count,
+            });
 
-                let progress_record_create_result = progress_records::create_progress_record(
-                    &db, &target_id, entry_date, value, item_name,
-                )
-                .await;
-                let mut progress_records = Vec::new();
-                progress_records.push(progress_record_create_result);
-                element!(progress_records::ProgressRecordsTable(progress_records: &progress_records, title: "progress record created")).print();
+            let days =
+                heatmap::get_daily_activity(&db, target.as_ref().map(|x| x.id), from, to).await?;
+            match output {
+                OutputFormat::Table => {
+                    let title = match &target {
+                        Some(target) => format!("{} in {}", target.name, year),
+                        None => format!("everything in {}", year),
+                    };
+                    element!(heatmap::Heatmap(days, from, to, measure, title)).print()
+                }
+                format => output::print_rows(format, &days)?,
             }
-            RecordCommands::Delete { id } => {
-                progress_records::delete_progress_record(&db, id).await;
-                println!("Record deleted");
+        }
+        Some(Commands::Stats {
+            target,
+            since,
+            until,
+            all,
+            status,
+            year,
+        }) => {
+            let checked_status = match status {
+                Some(x) => Some(targets::TargetStatus::from_str(x)?),
+                None if *all => None,
+                None => Some(targets::TargetStatus::Active),
+            };
+            let targets =
+                stats::get_stats_targets(&db, target.as_ref(), checked_status.as_ref(), *year)
+                    .await?;
+            let stats = stats::get_stats(&db, &targets, *since, *until).await?;
+            match output {
+                OutputFormat::Table => {
+                    element!(stats::StatsTable(stats: Some(&stats), title: "stats")).print()
+                }
+                format => output::print_rows(format, &stats)?,
+            }
+        }
+        Some(Commands::Import { action }) => match action {
+            ImportCommands::Csv {
+                file,
+                target,
+                target_column,
+                date_column,
+                item_column,
+                value_column,
+                date_format,
+                delimiter,
+                create_targets,
+                target_type,
+                target_value,
+                allow_duplicates,
+                dry_run,
+            } => {
+                let delimiter = u8::try_from(*delimiter).map_err(|_| {
+                    Error::Validation(String::from(
+                        "The delimiter has to be a single byte character",
+                    ))
+                })?;
+                let csv_options = import::CsvOptions {
+                    columns: import::CsvColumns {
+                        target: target_column.clone(),
+                        date: date_column.clone(),
+                        item_name: item_column.clone(),
+                        value: value_column.clone(),
+                    },
+                    // Resolved up front, so a prefix of its name works like everywhere else
+                    target: match target {
+                        Some(x) => Some(targets::resolve_target(&db, x).await?.id),
+                        None => None,
+                    },
+                    date_format: date_format.clone(),
+                    delimiter,
+                };
+                let options = import_options(
+                    *create_targets,
+                    target_type,
+                    *target_value,
+                    *allow_duplicates,
+                    *dry_run,
+                )?;
+                let rows = import::read_csv(file, &csv_options)?;
+                let report = import::import_rows(&db, rows, &options).await?;
+                print_import_report(&db, output, report, *dry_run).await?;
+            }
+            ImportCommands::Letterboxd {
+                file,
+                target,
+                all_years,
+                dry_run,
+            } => {
+                let target = targets::resolve_target(&db, target).await?;
+                import::validate_log_target(&target)?;
+                let rows = import::read_letterboxd(file, target.id)?;
+                let skipped = import::SkippedEntries::default();
+                import_log(&db, output, &target, rows, skipped, *all_years, *dry_run).await?;
+            }
+            ImportCommands::Goodreads {
+                file,
+                target,
+                all_years,
+                dry_run,
+            } => {
+                let target = targets::resolve_target(&db, target).await?;
+                import::validate_log_target(&target)?;
+                let mut skipped = import::SkippedEntries::default();
+                let rows = import::read_goodreads(file, target.id, &mut skipped)?;
+                import_log(&db, output, &target, rows, skipped, *all_years, *dry_run).await?;
+            }
+            ImportCommands::Json {
+                file,
+                replace,
+                dry_run,
+                yes,
+            } => {
+                let backup = backup::read_backup(&std::fs::read_to_string(file)?)?;
+                let mode = if *replace {
+                    backup::RestoreMode::Replace
+                } else {
+                    backup::RestoreMode::Merge
+                };
+                if *replace && !dry_run && !yes {
+                    let prompt = format!(
+                        "Delete every target and progress record, and restore {} targets and {} progress records from the backup?",
+                        backup.targets.len(),
+                        backup.progress_records.len()
+                    );
+                    if !confirm(&prompt)? {
+                        println!("Nothing restored");
+                        return Ok(());
+                    }
+                }
+
+                let summary = backup::restore(&db, &backup, mode, *dry_run).await?;
+                match output {
+                    OutputFormat::Table => {
+                        let title = if *dry_run {
+                            "would be restored (dry run)"
+                        } else {
+                            "restored"
+                        };
+                        element!(backup::RestoreSummaryTable(summary: &summary, title)).print()
+                    }
+                    format => output::print_rows(format, &[summary])?,
+                }
             }
         },
+        Some(Commands::Export) => {
+            let backup = backup::get_backup(&db).await?;
+            let json = serde_json::to_string_pretty(&backup).map_err(std::io::Error::from)?;
+            println!("{}", json);
+        }
+        Some(Commands::Rollover { year }) => {
+            let year = year.unwrap_or_else(years::this_year);
+            let rollover = years::rollover(&db, year).await?;
+            for target in &rollover.skipped {
+                eprintln!(
+                    "Skipped \"{}\", as {} already has a target with that name",
+                    target.name,
+                    year + 1
+                );
+            }
+            print_targets(
+                output,
+                rollover.created,
+                &format!("targets rolled over to {}", year + 1),
+            )?;
+        }
+        Some(Commands::Compare { year }) => {
+            let year = year.unwrap_or_else(years::this_year);
+            let comparisons = years::compare_years(&db, year).await?;
+            match output {
+                OutputFormat::Table => {
+                    let title = format!("{} vs {}", year - 1, year);
+                    element!(years::YearComparisonTable(comparisons: &comparisons, title: title.as_str())).print()
+                }
+                format => output::print_rows(format, &comparisons)?,
+            }
+        }
+        Some(Commands::Search {
+            query,
+            target,
+            year,
+            limit,
+        }) => {
+            let filter = search::SearchFilter {
+                target_id: match target {
+                    Some(x) => Some(targets::resolve_target(&db, x).await?.id),
+                    None => None,
+                },
+                year: *year,
+                limit: *limit,
+            };
+            let results = search::search(&db, query, &filter).await?;
+            match output {
+                OutputFormat::Table => {
+                    let title = format!("records matching \"{}\"", query);
+                    element!(search::SearchResultsTable(results: &results, title: title.as_str()))
+                        .print()
+                }
+                format => output::print_rows(format, &results)?,
+            }
+        }
+        Some(Commands::Chart { target }) => {
+            let target = targets::resolve_target(&db, target).await?;
+            let series = chart::get_progress_series(&db, &target).await?;
+            match output {
+                OutputFormat::Table => {
+                    let today = years::today();
+                    element!(chart::BurnUpChart(target, series, today)).print()
+                }
+                format => output::print_rows(format, &series)?,
+            }
+        }
+        Some(Commands::Profiles) | Some(Commands::Db { .. }) => {
+            unreachable!("handled before opening the database")
+        }
         None => {
-            let target_progresses = targets::get_progress_for_all_targets(&db).await;
-            app::run_app(target_progresses);
+            let status = if cli.all {
+                None
+            } else {
+                Some(&targets::TargetStatus::Active)
+            };
+            let year = cli.year.unwrap_or_else(years::this_year);
+            let target_progresses =
+                targets::get_progress_for_all_targets(&db, status, Some(year)).await?;
+            match output {
+                OutputFormat::Table => {
+                    app::run_app(db.clone(), status.cloned(), year, target_progresses).await?
+                }
+                format => output::print_rows(format, &target_progresses)?,
+            }
         }
     }
+
+    Ok(())
 }
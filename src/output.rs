@@ -0,0 +1,118 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    /// Uses the format asked for, otherwise a table when stdout is a terminal and
+    /// plain tab separated text when it's piped somewhere.
+    pub fn resolve(format: Option<OutputFormat>) -> OutputFormat {
+        match format {
+            Some(format) => format,
+            None if std::io::stdout().is_terminal() => OutputFormat::Table,
+            None => OutputFormat::Tsv,
+        }
+    }
+}
+
+/// Prints rows in one of the machine-readable formats. Tables are rendered by the
+/// callers since each one has its own component.
+pub fn print_rows<T: Serialize>(format: OutputFormat, rows: &[T]) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(rows).map_err(std::io::Error::from)?;
+            println!("{}", json);
+            Ok(())
+        }
+        OutputFormat::Csv => print_delimited(b',', rows),
+        OutputFormat::Tsv => print_delimited(b'\t', rows),
+        OutputFormat::Table => Err(Error::Validation(String::from(
+            "Tables can't be printed as plain rows",
+        ))),
+    }
+}
+
+fn print_delimited<T: Serialize>(delimiter: u8, rows: &[T]) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(std::io::stdout());
+
+    let mut flattened_rows = Vec::new();
+    for row in rows {
+        let mut fields = Map::new();
+        flatten(
+            "",
+            serde_json::to_value(row).map_err(std::io::Error::from)?,
+            &mut fields,
+        );
+        flattened_rows.push(fields);
+    }
+
+    // Optional fields can be left out of some rows, so the columns are every field any row has
+    let mut columns: Vec<&String> = Vec::new();
+    for fields in &flattened_rows {
+        for key in fields.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    if !flattened_rows.is_empty() {
+        writer.write_record(&columns).map_err(csv_error)?;
+    }
+    for fields in &flattened_rows {
+        writer
+            .write_record(
+                columns
+                    .iter()
+                    .map(|column| fields.get(*column).map(field_to_string).unwrap_or_default()),
+            )
+            .map_err(csv_error)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+// Nested structs become dotted columns, e.g. `pace.ahead_by`.
+fn flatten(prefix: &str, value: Value, fields: &mut Map<String, Value>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, fields);
+            }
+        }
+        value => {
+            fields.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+fn field_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(x) => x.clone(),
+        x => x.to_string(),
+    }
+}
+
+fn csv_error(error: csv::Error) -> Error {
+    Error::Io(error.into())
+}
@@ -0,0 +1,230 @@
+use chrono::{Days, NaiveDate};
+use iocraft::prelude::*;
+use serde::Serialize;
+
+use crate::targets::Target;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Pace {
+    /// Where a linear baseline from creation to the target date says we should be today.
+    pub expected_percentage: f64,
+    pub expected_value: f64,
+    /// How far the current value is ahead of the baseline, in target units. Negative when behind.
+    pub ahead_by: f64,
+    pub days_left: i64,
+    /// Rate still needed to hit the target on time, `None` once it's reached or the date has passed.
+    pub required_per_day: Option<f64>,
+    /// Finish date if progress carries on at the rate so far, `None` once reached, with no
+    /// progress, or on the day the target was made, when there's no rate to go on yet.
+    pub projected_finish: Option<NaiveDate>,
+}
+
+/// How far along a linear baseline from creation to the target date says the target
+/// should be on `date`, as a percentage. Stays at 0 before creation and 100 after the date.
+pub fn expected_percentage(target: &Target, date: NaiveDate) -> f64 {
+    let start_date = target.created_at.date();
+    let total_days = (target.target_date - start_date).num_days().max(1);
+    let elapsed_days = (date - start_date).num_days().clamp(0, total_days);
+    elapsed_days as f64 / total_days as f64 * 100.0
+}
+
+/// The value the baseline says the target should have on `date`.
+pub fn expected_value(target: &Target, date: NaiveDate) -> f64 {
+    target.start_value
+        + (target.target_value - target.start_value) * expected_percentage(target, date) / 100.0
+}
+
+impl Pace {
+    pub fn for_target(target: &Target, today: NaiveDate) -> Pace {
+        let start_date = target.created_at.date();
+        let days_left = (target.target_date - today).num_days().max(0);
+
+        let expected_percentage = expected_percentage(target, today);
+        let expected_value = expected_value(target, today);
+
+        let direction = target.direction();
+        let ahead_by = (target.current_value - expected_value) * direction;
+        let travelled = (target.current_value - target.start_value) * direction;
+        let remaining = (target.target_value - target.current_value) * direction;
+
+        let required_per_day = if remaining > 0.0 && days_left > 0 {
+            Some(remaining / days_left as f64)
+        } else {
+            None
+        };
+
+        // The rate is over every day so far, including any after the target date
+        let days_so_far = (today - start_date).num_days();
+        let rate = travelled / days_so_far.max(1) as f64;
+        let projected_finish = if remaining > 0.0 && rate > 0.0 && days_so_far > 0 {
+            today.checked_add_days(Days::new((remaining / rate).ceil() as u64))
+        } else {
+            None
+        };
+
+        Pace {
+            expected_percentage,
+            expected_value,
+            ahead_by,
+            days_left,
+            required_per_day,
+            projected_finish,
+        }
+    }
+
+    pub fn is_ahead(&self) -> bool {
+        self.ahead_by >= 0.0
+    }
+
+    pub fn required_per_week(&self) -> Option<f64> {
+        self.required_per_day.map(|per_day| per_day * 7.0)
+    }
+
+    pub fn summary(&self) -> String {
+        let status = if self.is_ahead() {
+            format!("ahead by {:.1}", self.ahead_by)
+        } else {
+            format!("behind by {:.1}", -self.ahead_by)
+        };
+        let required = match self.required_per_day {
+            Some(per_day) if per_day < 1.0 => format!("{:.1}/week needed", per_day * 7.0),
+            Some(per_day) => format!("{:.1}/day needed", per_day),
+            None => String::from("nothing more needed"),
+        };
+        let finish = match self.projected_finish {
+            Some(date) => format!("finish {}", date),
+            None => String::from("no projection"),
+        };
+        format!("{} · {} · {}", status, required, finish)
+    }
+}
+
+#[derive(Default, Props)]
+pub struct PaceDetailProps {
+    pub pace: Option<Pace>,
+}
+
+#[component]
+pub fn PaceDetail(props: &PaceDetailProps) -> impl Into<AnyElement<'static>> {
+    let rows = match &props.pace {
+        Some(pace) => vec![
+            (
+                "expected today",
+                format!(
+                    "{:.1} ({:.1}%)",
+                    pace.expected_value, pace.expected_percentage
+                ),
+            ),
+            (
+                if pace.is_ahead() {
+                    "ahead by"
+                } else {
+                    "behind by"
+                },
+                format!("{:.1}", pace.ahead_by.abs()),
+            ),
+            ("days left", pace.days_left.to_string()),
+            (
+                "needed per day",
+                pace.required_per_day
+                    .map(|x| format!("{:.2}", x))
+                    .unwrap_or_else(|| "N/A".to_string()),
+            ),
+            (
+                "needed per week",
+                pace.required_per_week()
+                    .map(|x| format!("{:.2}", x))
+                    .unwrap_or_else(|| "N/A".to_string()),
+            ),
+            (
+                "projected finish",
+                pace.projected_finish
+                    .map(|x| x.to_string())
+                    .unwrap_or_else(|| "N/A".to_string()),
+            ),
+        ],
+        None => vec![],
+    };
+
+    element! {
+        View(flex_direction: FlexDirection::Column) {
+            #(rows.into_iter().map(|(label, value)| element! {
+                View {
+                    View(width: 20) {
+                        Text(content: label, weight: Weight::Bold)
+                    }
+                    Text(content: value)
+                }
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::targets::tests::target;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn increasing_target_ahead_of_pace() {
+        // 100 days to get from 0 to 100, with 60 done after 50 days
+        let target = target(date(2026, 1, 1), date(2026, 4, 11), 0.0, 100.0, 60.0);
+        let pace = Pace::for_target(&target, date(2026, 2, 20));
+        assert_eq!(pace.expected_percentage, 50.0);
+        assert_eq!(pace.expected_value, 50.0);
+        assert_eq!(pace.ahead_by, 10.0);
+        assert!(pace.is_ahead());
+        assert_eq!(pace.days_left, 50);
+        assert_eq!(pace.required_per_day, Some(0.8));
+        // 1.2 a day leaves the last 40 for 34 more days
+        assert_eq!(pace.projected_finish, Some(date(2026, 3, 26)));
+    }
+
+    #[test]
+    fn decreasing_target_behind_pace() {
+        // 100 days to get from 90 down to 80, with 2 lost after 50 days
+        let target = target(date(2026, 1, 1), date(2026, 4, 11), 90.0, 80.0, 88.0);
+        let pace = Pace::for_target(&target, date(2026, 2, 20));
+        assert_eq!(pace.expected_value, 85.0);
+        assert_eq!(pace.ahead_by, -3.0);
+        assert!(!pace.is_ahead());
+        assert_eq!(pace.required_per_day, Some(0.16));
+        // 0.04 a day leaves the last 8 for 200 more days
+        assert_eq!(pace.projected_finish, Some(date(2026, 9, 8)));
+    }
+
+    #[test]
+    fn no_projection_on_the_day_the_target_is_made() {
+        let target = target(date(2026, 10, 18), date(2026, 12, 31), 0.0, 52.0, 2.0);
+        let pace = Pace::for_target(&target, date(2026, 10, 18));
+        assert_eq!(pace.expected_percentage, 0.0);
+        assert_eq!(pace.ahead_by, 2.0);
+        assert_eq!(pace.projected_finish, None);
+        assert_eq!(pace.days_left, 74);
+    }
+
+    #[test]
+    fn past_target_date() {
+        // Due after 100 days but 200 have gone by, with half done
+        let target = target(date(2026, 1, 1), date(2026, 4, 11), 0.0, 100.0, 50.0);
+        let pace = Pace::for_target(&target, date(2026, 7, 20));
+        assert_eq!(pace.expected_percentage, 100.0);
+        assert_eq!(pace.ahead_by, -50.0);
+        assert_eq!(pace.days_left, 0);
+        assert_eq!(pace.required_per_day, None);
+        // 0.25 a day over all 200 days, not just the 100 before the target date
+        assert_eq!(pace.projected_finish, Some(date(2027, 2, 5)));
+    }
+
+    #[test]
+    fn reached_target_needs_nothing_more() {
+        let target = target(date(2026, 1, 1), date(2026, 4, 11), 0.0, 100.0, 100.0);
+        let pace = Pace::for_target(&target, date(2026, 2, 20));
+        assert_eq!(pace.required_per_day, None);
+        assert_eq!(pace.projected_finish, None);
+    }
+}
@@ -58,7 +58,7 @@ pub fn StaticProgressBar(
                 Text(content: format!("{}", props.title))
             }
             View(border_style: BorderStyle::Round, border_color: Color::Blue, width: 50, align_items: AlignItems::Center) {
-                View(width: Percent(props.progress_percentage as f32), height: 1, background_color: Color::Green,)
+                View(width: Percent(props.progress_percentage.clamp(0.0, 100.0) as f32), height: 1, background_color: Color::Green,)
             }
             View(padding: 0, width: 10, padding_left: 4) {
                 Text(content: format!("{}", props.target))
@@ -1,15 +1,58 @@
 use chrono::NaiveDate;
 use iocraft::prelude::*;
+use serde::Serialize;
 use sqlx::FromRow;
-use sqlx::{ Pool, Sqlite };
+use sqlx::{ Executor, Pool, QueryBuilder, Sqlite };
 
-#[derive(Clone, FromRow, Debug)]
+use crate::duplicates;
+use crate::error::{ Error, Result };
+use crate::targets::{ self, TargetType };
+use crate::years;
+
+#[derive(Clone, FromRow, Debug, Serialize)]
 pub struct ProgressRecord {
-    id: i64,
-    target_id: i64,
-    entry_date: chrono::NaiveDate,
-    value: f64,
-    item_name: Option<String>,
+    pub id: i64,
+    pub target_id: i64,
+    pub entry_date: chrono::NaiveDate,
+    pub value: Option<f64>,
+    pub item_name: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum ProgressRecordSort {
+    Id,
+    #[default]
+    EntryDate,
+    Value,
+    ItemName,
+}
+
+impl ProgressRecordSort {
+    fn column(&self) -> &'static str {
+        match self {
+            ProgressRecordSort::Id => "pr.id",
+            ProgressRecordSort::EntryDate => "pr.entry_date",
+            ProgressRecordSort::Value => "pr.value",
+            ProgressRecordSort::ItemName => "pr.item_name",
+        }
+    }
+}
+
+/// Which progress records to list. Anything left as `None` isn't filtered on.
+#[derive(Default)]
+pub struct ProgressRecordFilter {
+    pub target_id: Option<i64>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    /// Only records for targets in this year.
+    pub year: Option<i32>,
+    pub item_name: Option<String>,
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+    pub sort: ProgressRecordSort,
+    pub descending: bool,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
 }
 
 #[derive(Default, Props)]
@@ -75,7 +118,7 @@ pub fn ProgressRecordsTable<'a>(
                     }
 
                     View(width: 25pct, justify_content: JustifyContent::Center) {
-                        Text(content: progress_record.value.to_string())
+                        Text(content: progress_record.value.map(|x| x.to_string()).unwrap_or_else(|| "N/A".to_string()))
                     }
                 }
             })).into_iter().flatten())
@@ -83,15 +126,33 @@ pub fn ProgressRecordsTable<'a>(
     }
 }
 
-pub async fn create_progress_record(
-    db: &Pool<Sqlite>,
+/// Checks a record has what its target's type needs, an item name to count or a value to track.
+pub fn validate_progress_record(
+    target_type: &TargetType,
+    item_name: &Option<String>,
+    value: &Option<f64>
+) -> Result<()> {
+    match target_type {
+        TargetType::Count if item_name.is_none() => Err(Error::Validation(String::from("Item name is required for count targets"))),
+        TargetType::Value if value.is_none() => Err(Error::Validation(String::from("Value is required for value targets"))),
+        TargetType::Sum if value.is_none() => Err(Error::Validation(String::from("Value is required for sum targets"))),
+        _ => Ok(()),
+    }
+}
+
+/// Works with a pool or an open transaction, e.g. to import many records at once.
+pub async fn create_progress_record<'e, E>(
+    db: E,
     target_id: &i64,
     entry_date: &Option<NaiveDate>,
     value: &Option<f64>,
     item_name: &Option<String>
-) -> ProgressRecord {
-    let today = chrono::Utc::now().date_naive();
-    sqlx::query_as::<_, ProgressRecord>(
+) -> Result<ProgressRecord>
+where
+    E: Executor<'e, Database = Sqlite>
+{
+    let today = years::today();
+    let progress_record = sqlx::query_as::<_, ProgressRecord>(
         "INSERT INTO progress_records (target_id, entry_date, value, item_name)
                     VALUES ($1, $2, $3, $4)
                     RETURNING *;"
@@ -103,16 +164,122 @@ pub async fn create_progress_record(
         })
         .bind(value)
         .bind(item_name)
-        .fetch_one(db).await
-        .unwrap()
+        .fetch_one(db).await?;
+    Ok(progress_record)
+}
+
+pub async fn get_progress_record<'e, E>(db: E, id: &i64) -> Result<ProgressRecord>
+where
+    E: Executor<'e, Database = Sqlite>
+{
+    sqlx::query_as::<_, ProgressRecord>("SELECT * FROM progress_records WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db).await?
+        .ok_or_else(|| Error::NotFound(format!("No progress record with id {}", id)))
+}
+
+/// Works with a pool or an open transaction, e.g. to see the records it's added.
+pub async fn get_progress_records_for_target<'e, E>(
+    db: E,
+    target_id: &i64
+) -> Result<Vec<ProgressRecord>>
+where
+    E: Executor<'e, Database = Sqlite>
+{
+    let progress_records = sqlx::query_as::<_, ProgressRecord>(
+        "SELECT * FROM progress_records WHERE target_id = $1 ORDER BY entry_date, id"
+    )
+        .bind(target_id)
+        .fetch_all(db).await?;
+    Ok(progress_records)
+}
+
+pub async fn get_progress_records(
+    db: &Pool<Sqlite>,
+    filter: &ProgressRecordFilter
+) -> Result<Vec<ProgressRecord>> {
+    let mut query = QueryBuilder::<Sqlite>::new(
+        "SELECT pr.* FROM progress_records pr WHERE 1 = 1"
+    );
+    if let Some(target_id) = filter.target_id {
+        query.push(" AND pr.target_id = ").push_bind(target_id);
+    }
+    if let Some(since) = filter.since {
+        query.push(" AND pr.entry_date >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        query.push(" AND pr.entry_date <= ").push_bind(until);
+    }
+    if let Some(year) = filter.year {
+        query.push(" AND pr.target_id IN (SELECT t.id FROM targets t WHERE t.year = ").push_bind(year).push(")");
+    }
+    if let Some(item_name) = &filter.item_name {
+        // instr rather than LIKE, so a "%" or "_" in the name is matched as it is
+        query.push(" AND instr(lower(pr.item_name), lower(").push_bind(item_name.clone()).push(")) > 0");
+    }
+    if let Some(min_value) = filter.min_value {
+        query.push(" AND pr.value >= ").push_bind(min_value);
+    }
+    if let Some(max_value) = filter.max_value {
+        query.push(" AND pr.value <= ").push_bind(max_value);
+    }
+
+    let direction = if filter.descending { "DESC" } else { "ASC" };
+    query.push(format!(" ORDER BY {} {}, pr.id {}", filter.sort.column(), direction, direction));
+
+    // SQLite needs a LIMIT before it'll accept an OFFSET, and -1 means no limit
+    if filter.limit.is_some() || filter.offset.is_some() {
+        query.push(" LIMIT ").push_bind(filter.limit.unwrap_or(-1));
+        query.push(" OFFSET ").push_bind(filter.offset.unwrap_or(0));
+    }
+
+    let progress_records = query.build_query_as::<ProgressRecord>().fetch_all(db).await?;
+    Ok(progress_records)
 }
 
-pub async fn get_progress_records(db: &Pool<Sqlite>) -> Vec<ProgressRecord> {
-    sqlx::query_as::<_, ProgressRecord>("SELECT * FROM progress_records")
-        .fetch_all(db).await
-        .unwrap()
+/// Updates only the fields that are given, leaving the rest as they are. The record has to
+/// still make sense for its target's type, and can't be renamed to something the target
+/// already has unless `allow_duplicate` is set.
+pub async fn update_progress_record(
+    db: &Pool<Sqlite>,
+    id: &i64,
+    entry_date: &Option<NaiveDate>,
+    value: &Option<f64>,
+    item_name: &Option<String>,
+    allow_duplicate: bool
+) -> Result<ProgressRecord> {
+    let mut tx = db.begin().await?;
+    let progress_record = get_progress_record(&mut *tx, id).await?;
+    let target = targets::get_target(&mut *tx, &progress_record.target_id).await?;
+    validate_progress_record(
+        &target.target_type,
+        &item_name.clone().or(progress_record.item_name),
+        &value.or(progress_record.value)
+    )?;
+    duplicates::check_duplicate(&mut *tx, &target, item_name, Some(*id), allow_duplicate).await?;
+
+    let progress_record = sqlx::query_as::<_, ProgressRecord>(
+        "UPDATE progress_records
+                    SET entry_date = COALESCE($2, entry_date),
+                        value = COALESCE($3, value),
+                        item_name = COALESCE($4, item_name)
+                    WHERE id = $1
+                    RETURNING *;"
+    )
+        .bind(id)
+        .bind(entry_date)
+        .bind(value)
+        .bind(item_name)
+        .fetch_optional(&mut *tx).await?
+        .ok_or_else(|| Error::NotFound(format!("No progress record with id {}", id)))?;
+    tx.commit().await?;
+    Ok(progress_record)
 }
 
-pub async fn delete_progress_record(db: &Pool<Sqlite>, id: &i64) {
-    sqlx::query("DELETE FROM progress_records WHERE id = $1").bind(id).execute(db).await.unwrap();
+pub async fn delete_progress_record(db: &Pool<Sqlite>, id: &i64) -> Result<()> {
+    let result = sqlx::query("DELETE FROM progress_records WHERE id = $1").bind(id).execute(db).await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound(format!("No progress record with id {}", id)));
+    }
+    Ok(())
 }
@@ -0,0 +1,179 @@
+use chrono::NaiveDate;
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{FromRow, Pool, QueryBuilder, Sqlite};
+
+use crate::error::{Error, Result};
+
+// Put around the matched words by SQLite's highlight(), and picked out again for the table
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+
+#[derive(Clone, Debug, Serialize, FromRow)]
+pub struct SearchResult {
+    pub id: i64,
+    pub target_id: i64,
+    pub target_name: String,
+    pub entry_date: NaiveDate,
+    pub value: Option<f64>,
+    pub item_name: String,
+    /// The item name with the matched words between `HIGHLIGHT_START` and `HIGHLIGHT_END`.
+    #[serde(skip)]
+    pub highlighted: String,
+    /// How well the record matches, lower is better.
+    pub rank: f64,
+}
+
+impl SearchResult {
+    /// The item name in pieces, with whether each piece matched.
+    fn highlighted_segments(&self) -> Vec<(String, bool)> {
+        let mut segments = Vec::new();
+        let mut is_match = false;
+        for (i, text) in self
+            .highlighted
+            .split([HIGHLIGHT_START, HIGHLIGHT_END])
+            .enumerate()
+        {
+            if i > 0 {
+                is_match = !is_match;
+            }
+            if !text.is_empty() {
+                segments.push((text.to_string(), is_match));
+            }
+        }
+        segments
+    }
+}
+
+/// Which records to search, and how many results to return.
+pub struct SearchFilter {
+    pub target_id: Option<i64>,
+    /// Only records for targets in this year.
+    pub year: Option<i32>,
+    pub limit: i64,
+}
+
+/// Turns what was typed into an FTS5 query that finds records with every word in it, or
+/// words starting with the last one, so punctuation like "Paris, Texas (1984)" can't
+/// break the query's syntax.
+fn match_query(query: &str) -> Result<String> {
+    let words: Vec<&str> = query
+        .split(|x: char| !x.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .collect();
+    match words.split_last() {
+        Some((last, rest)) => Ok(rest
+            .iter()
+            .map(|x| format!("\"{}\"", x))
+            .chain([format!("\"{}\"*", last)])
+            .collect::<Vec<_>>()
+            .join(" ")),
+        None => Err(Error::Validation(String::from(
+            "Search for at least one word or number",
+        ))),
+    }
+}
+
+/// Records whose item names match the query, best matches first.
+pub async fn search(
+    db: &Pool<Sqlite>,
+    query: &str,
+    filter: &SearchFilter,
+) -> Result<Vec<SearchResult>> {
+    let mut builder = QueryBuilder::<Sqlite>::new(format!(
+        "SELECT
+            pr.id,
+            pr.target_id,
+            t.name AS target_name,
+            pr.entry_date,
+            pr.value,
+            pr.item_name,
+            highlight(progress_records_fts, 0, '{}', '{}') AS highlighted,
+            bm25(progress_records_fts) AS rank
+        FROM progress_records_fts
+        JOIN progress_records pr ON pr.id = progress_records_fts.rowid
+        JOIN targets t ON t.id = pr.target_id
+        WHERE progress_records_fts MATCH ",
+        HIGHLIGHT_START, HIGHLIGHT_END
+    ));
+    builder.push_bind(match_query(query)?);
+    if let Some(target_id) = filter.target_id {
+        builder.push(" AND pr.target_id = ").push_bind(target_id);
+    }
+    if let Some(year) = filter.year {
+        builder.push(" AND t.year = ").push_bind(year);
+    }
+    builder
+        .push(" ORDER BY rank, pr.entry_date DESC, pr.id DESC LIMIT ")
+        .push_bind(filter.limit);
+
+    let results = builder
+        .build_query_as::<SearchResult>()
+        .fetch_all(db)
+        .await?;
+    Ok(results)
+}
+
+#[derive(Default, Props)]
+pub struct SearchResultsTableProps<'a> {
+    pub results: Option<&'a Vec<SearchResult>>,
+    pub title: &'a str,
+}
+
+#[component]
+pub fn SearchResultsTable<'a>(props: &SearchResultsTableProps<'a>) -> impl Into<AnyElement<'a>> {
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            flex_direction: FlexDirection::Column,
+            width: 100,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom:1, ) {
+                Text(content: props.title, weight: Weight::Bold )
+            }
+
+            View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
+                View(width: 10pct, justify_content: JustifyContent::Center) {
+                    Text(content: "id", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 20pct, justify_content: JustifyContent::Center) {
+                    Text(content: "target", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 15pct, justify_content: JustifyContent::Center) {
+                    Text(content: "entry date", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 55pct, justify_content: JustifyContent::Center) {
+                    Text(content: "item name", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+            }
+
+            #(props.results.map(|results| results.iter().enumerate().map(|(i, result)| element! {
+                View(background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) }) {
+                    View(width: 10pct, justify_content: JustifyContent::Center) {
+                        Text(content: result.id.to_string())
+                    }
+                    View(width: 20pct, justify_content: JustifyContent::Center) {
+                        Text(content: result.target_name.clone())
+                    }
+                    View(width: 15pct, justify_content: JustifyContent::Center) {
+                        Text(content: result.entry_date.to_string())
+                    }
+                    View(width: 55pct, justify_content: JustifyContent::Center) {
+                        #(result.highlighted_segments().into_iter().enumerate().map(|(i, (text, is_match))| element! {
+                            View(key: i, width: text.chars().count() as u32) {
+                                Text(
+                                    content: text,
+                                    color: if is_match { Some(Color::Yellow) } else { None },
+                                    weight: if is_match { Weight::Bold } else { Weight::Normal },
+                                )
+                            }
+                        }))
+                    }
+                }
+            })).into_iter().flatten())
+        }
+    }
+}
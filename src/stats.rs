@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::error::Result;
+use crate::progress_records::{self, ProgressRecord, ProgressRecordFilter};
+use crate::targets::{self, Target, TargetStatus, TargetType};
+use crate::years;
+
+const DAYS_PER_MONTH: f64 = 365.25 / 12.0;
+
+/// A summary of the records for one target, or for all of them when `target_id` is `None`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Stats {
+    pub target_id: Option<i64>,
+    pub name: String,
+    pub record_count: usize,
+    pub total_value: f64,
+    /// The mean of the records that have a value.
+    pub average_value: Option<f64>,
+    pub records_per_week: f64,
+    pub records_per_month: f64,
+    /// Months are compared by total value for "sum" targets and by how many records there
+    /// are otherwise. Like "2026-03".
+    pub best_month: Option<String>,
+    pub worst_month: Option<String>,
+    /// Like "Mon".
+    pub busiest_weekday: Option<String>,
+    /// Counted back from today, whatever the date range.
+    pub days_since_last_entry: Option<i64>,
+    /// Habits are left out, as they start again each period.
+    pub percentage_complete: Option<f64>,
+    /// How much of the time between creation and the target date has gone by.
+    pub percentage_elapsed: Option<f64>,
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (total, count) = values.fold((0.0, 0), |(total, count), x| (total + x, count + 1));
+    (count > 0).then(|| total / count as f64)
+}
+
+impl Stats {
+    /// `from` and `to` are the days the rates are worked out over, both included.
+    fn new(
+        target_id: Option<i64>,
+        name: String,
+        progress_records: &[&ProgressRecord],
+        by_value: bool,
+        from: NaiveDate,
+        to: NaiveDate,
+        today: NaiveDate,
+    ) -> Stats {
+        let days = ((to - from).num_days() + 1).max(1) as f64;
+        let values = progress_records.iter().filter_map(|x| x.value);
+
+        // Every month in the range is counted, so one without records can be the worst
+        let mut months: BTreeMap<(i32, u32), f64> = BTreeMap::new();
+        let mut month = from.with_day(1).unwrap();
+        while month <= to {
+            months.insert((month.year(), month.month()), 0.0);
+            month = month + chrono::Months::new(1);
+        }
+        let mut weekdays: HashMap<Weekday, usize> = HashMap::new();
+        for progress_record in progress_records {
+            let date = progress_record.entry_date;
+            *months.entry((date.year(), date.month())).or_default() += if by_value {
+                progress_record.value.unwrap_or(0.0)
+            } else {
+                1.0
+            };
+            *weekdays.entry(date.weekday()).or_default() += 1;
+        }
+        let month_name = |(year, month): &(i32, u32)| format!("{}-{:02}", year, month);
+        let has_records = !progress_records.is_empty();
+        // The earliest month wins a tie
+        let best_month = months
+            .iter()
+            .rev()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .filter(|_| has_records)
+            .map(|(month, _)| month_name(month));
+        let worst_month = months
+            .iter()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .filter(|_| has_records)
+            .map(|(month, _)| month_name(month));
+        let busiest_weekday = weekdays
+            .into_iter()
+            .max_by_key(|(weekday, count)| {
+                (*count, std::cmp::Reverse(weekday.num_days_from_monday()))
+            })
+            .map(|(weekday, _)| weekday.to_string());
+
+        Stats {
+            target_id,
+            name,
+            record_count: progress_records.len(),
+            total_value: values.clone().fold(0.0, |total, x| total + x),
+            average_value: mean(values),
+            records_per_week: progress_records.len() as f64 / days * 7.0,
+            records_per_month: progress_records.len() as f64 / days * DAYS_PER_MONTH,
+            best_month,
+            worst_month,
+            busiest_weekday,
+            days_since_last_entry: progress_records
+                .iter()
+                .map(|x| x.entry_date)
+                .max()
+                .map(|x| (today - x).num_days()),
+            percentage_complete: None,
+            percentage_elapsed: None,
+        }
+    }
+}
+
+/// Stats for each target, followed by one for all of them together. Only records from
+/// `since` to `until` count, and the rates are worked out over that range, or from the
+/// earliest record or target up to today where it's open.
+pub async fn get_stats(
+    db: &Pool<Sqlite>,
+    targets: &[Target],
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Result<Vec<Stats>> {
+    let today = years::today();
+    let progress_records = progress_records::get_progress_records(
+        db,
+        &ProgressRecordFilter {
+            target_id: match targets {
+                [target] => Some(target.id),
+                _ => None,
+            },
+            since,
+            until,
+            ..Default::default()
+        },
+    )
+    .await?;
+    let mut records_by_target: HashMap<i64, Vec<&ProgressRecord>> = HashMap::new();
+    for progress_record in &progress_records {
+        records_by_target
+            .entry(progress_record.target_id)
+            .or_default()
+            .push(progress_record);
+    }
+
+    let range = |targets: &[&Target], progress_records: &[&ProgressRecord]| {
+        let from = since.unwrap_or_else(|| {
+            progress_records
+                .iter()
+                .map(|x| x.entry_date)
+                .chain(targets.iter().map(|x| x.created_at.date()))
+                .min()
+                .unwrap_or(today)
+        });
+        (from, until.unwrap_or(today).max(from))
+    };
+
+    let mut stats = Vec::new();
+    let mut all_records = Vec::new();
+    for target in targets {
+        let target_records = records_by_target.remove(&target.id).unwrap_or_default();
+        let (from, to) = range(&[target], &target_records);
+        let is_habit = target.period.is_some();
+        stats.push(Stats {
+            percentage_complete: (!is_habit).then(|| target.progress_percentage()),
+            percentage_elapsed: (!is_habit).then(|| target.pace().expected_percentage),
+            ..Stats::new(
+                Some(target.id),
+                target.name.clone(),
+                &target_records,
+                matches!(target.target_type, TargetType::Sum),
+                from,
+                to,
+                today,
+            )
+        });
+        all_records.extend(target_records);
+    }
+
+    let (from, to) = range(&targets.iter().collect::<Vec<_>>(), &all_records);
+    stats.push(Stats {
+        percentage_complete: mean(stats.iter().filter_map(|x| x.percentage_complete)),
+        percentage_elapsed: mean(stats.iter().filter_map(|x| x.percentage_elapsed)),
+        ..Stats::new(
+            None,
+            String::from("all targets"),
+            &all_records,
+            false,
+            from,
+            to,
+            today,
+        )
+    });
+    Ok(stats)
+}
+
+/// The targets to work out stats for: one given by id or name, or all with a status and year.
+pub async fn get_stats_targets(
+    db: &Pool<Sqlite>,
+    target: Option<&String>,
+    status: Option<&TargetStatus>,
+    year: Option<i32>,
+) -> Result<Vec<Target>> {
+    match target {
+        Some(target) => Ok(vec![targets::resolve_target(db, target).await?]),
+        None => targets::get_targets(db, status, year).await,
+    }
+}
+
+#[derive(Default, Props)]
+pub struct StatsTableProps<'a> {
+    pub stats: Option<&'a Vec<Stats>>,
+    pub title: &'a str,
+}
+
+#[component]
+pub fn StatsTable<'a>(props: &StatsTableProps<'a>) -> impl Into<AnyElement<'a>> {
+    let optional = |x: Option<String>| x.unwrap_or_else(|| "N/A".to_string());
+    let columns: [(&str, u16); 10] = [
+        ("name", 14),
+        ("records", 7),
+        ("per week", 8),
+        ("per month", 9),
+        ("average", 8),
+        ("best month", 10),
+        ("worst month", 11),
+        ("busiest day", 11),
+        ("last entry", 10),
+        ("done / elapsed", 12),
+    ];
+    let rows: Vec<[String; 10]> = props
+        .stats
+        .map(|stats| {
+            stats
+                .iter()
+                .map(|x| {
+                    [
+                        x.name.clone(),
+                        x.record_count.to_string(),
+                        format!("{:.1}", x.records_per_week),
+                        format!("{:.1}", x.records_per_month),
+                        optional(x.average_value.map(|x| format!("{:.1}", x))),
+                        optional(x.best_month.clone()),
+                        optional(x.worst_month.clone()),
+                        optional(x.busiest_weekday.clone()),
+                        optional(x.days_since_last_entry.map(|x| match x {
+                            0 => String::from("today"),
+                            1 => String::from("1 day ago"),
+                            x => format!("{} days ago", x),
+                        })),
+                        match (x.percentage_complete, x.percentage_elapsed) {
+                            (Some(complete), Some(elapsed)) => {
+                                format!("{:.1}% / {:.1}%", complete, elapsed)
+                            }
+                            _ => String::from("N/A"),
+                        },
+                    ]
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let row_count = rows.len();
+
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            flex_direction: FlexDirection::Column,
+            width: 120,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom:1, ) {
+                Text(content: props.title, weight: Weight::Bold )
+            }
+
+            View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
+                #(columns.iter().map(|(label, width)| element! {
+                    View(width: Percent(*width as f32), justify_content: JustifyContent::Center) {
+                        Text(content: *label, weight: Weight::Bold, decoration: TextDecoration::Underline)
+                    }
+                }))
+            }
+
+            #(rows.into_iter().enumerate().map(|(i, row)| element! {
+                View(
+                    background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) },
+                    // The last row is for all the targets together
+                    border_style: if i + 1 == row_count { BorderStyle::Single } else { BorderStyle::None },
+                    border_edges: Edges::Top,
+                    border_color: Color::Grey,
+                ) {
+                    #(row.into_iter().zip(columns).map(|(value, (_, width))| element! {
+                        View(width: Percent(width as f32), justify_content: JustifyContent::Center) {
+                            Text(content: value, weight: if i + 1 == row_count { Weight::Bold } else { Weight::Normal })
+                        }
+                    }))
+                }
+            }))
+        }
+    }
+}
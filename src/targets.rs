@@ -1,14 +1,26 @@
+use std::fmt;
 use std::str::FromStr;
 
-use chrono::{Datelike, NaiveDate};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
 use iocraft::prelude::*;
-use sqlx::{FromRow, Pool, Sqlite};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, Executor, FromRow, Pool, Sqlite};
 
-#[derive(Clone, Debug, sqlx::Type)]
+use crate::chart;
+use crate::error::{Error, Result};
+use crate::habits::{self, HabitDetail, HabitProgress, Period};
+use crate::pace::{Pace, PaceDetail};
+use crate::progress_bar;
+use crate::progress_records;
+use crate::years;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "target_type", rename_all = "lowercase")]
 pub enum TargetType {
     Count,
     Value,
+    Sum,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -17,26 +29,190 @@ pub struct ParseTargetTypeError;
 impl FromStr for TargetType {
     type Err = ParseTargetTypeError;
 
-    fn from_str(input: &str) -> Result<TargetType, Self::Err> {
+    fn from_str(input: &str) -> std::result::Result<TargetType, Self::Err> {
         match input {
             "count" => Ok(TargetType::Count),
             "value" => Ok(TargetType::Value),
+            "sum" => Ok(TargetType::Sum),
             _ => Err(ParseTargetTypeError),
         }
     }
 }
 
-#[derive(Clone, FromRow, Debug)]
+impl fmt::Display for TargetType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TargetType::Count => write!(f, "count"),
+            TargetType::Value => write!(f, "value"),
+            TargetType::Sum => write!(f, "sum"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "status", rename_all = "lowercase")]
+pub enum TargetStatus {
+    Active,
+    Completed,
+    Abandoned,
+    Archived,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseTargetStatusError;
+
+impl FromStr for TargetStatus {
+    type Err = ParseTargetStatusError;
+
+    fn from_str(input: &str) -> std::result::Result<TargetStatus, Self::Err> {
+        match input {
+            "active" => Ok(TargetStatus::Active),
+            "completed" => Ok(TargetStatus::Completed),
+            "abandoned" => Ok(TargetStatus::Abandoned),
+            "archived" => Ok(TargetStatus::Archived),
+            _ => Err(ParseTargetStatusError),
+        }
+    }
+}
+
+impl fmt::Display for TargetStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TargetStatus::Active => write!(f, "active"),
+            TargetStatus::Completed => write!(f, "completed"),
+            TargetStatus::Abandoned => write!(f, "abandoned"),
+            TargetStatus::Archived => write!(f, "archived"),
+        }
+    }
+}
+
+#[derive(Clone, FromRow, Debug, Serialize)]
 pub struct Target {
     pub id: i64,
     pub name: String,
+    pub created_at: chrono::NaiveDateTime,
     pub target_date: chrono::NaiveDate,
-    pub status: String,
+    pub status: TargetStatus,
     pub start_value: f64,
     pub target_value: f64,
     pub target_type: TargetType,
+    pub auto_complete: bool,
+    /// Set for habits, which repeat with the target value as the quota for each period.
+    pub period: Option<Period>,
+    /// The year whose set of goals the target belongs to.
+    pub year: i32,
+    pub current_value: f64,
+    pub status_changed_at: Option<chrono::NaiveDateTime>,
+}
+
+impl Target {
+    pub fn progress_percentage(&self) -> f64 {
+        progress_percentage(self.start_value, self.target_value, self.current_value)
+    }
+
+    /// 1 for targets that go up, and -1 for ones that go down, e.g. 90kg down to 80kg,
+    /// which make progress when the value goes down.
+    pub fn direction(&self) -> f64 {
+        if self.target_value < self.start_value {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+
+    pub fn pace(&self) -> Pace {
+        Pace::for_target(self, years::today())
+    }
+
+    /// The day the target was made, or of its first record if that's earlier, e.g. for
+    /// records logged from before it was added.
+    pub fn first_date(&self, progress_records: &[progress_records::ProgressRecord]) -> NaiveDate {
+        progress_records
+            .iter()
+            .map(|x| x.entry_date)
+            .chain([self.created_at.date()])
+            .min()
+            .unwrap()
+    }
+}
+
+/// A target along with its progress, for machine-readable output.
+#[derive(Serialize)]
+pub struct TargetOutput<'a> {
+    #[serde(flatten)]
+    pub target: &'a Target,
+    pub progress_percentage: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub habit: Option<HabitProgress>,
+}
+
+impl<'a> From<&'a Target> for TargetOutput<'a> {
+    fn from(target: &'a Target) -> Self {
+        TargetOutput {
+            target,
+            progress_percentage: target.progress_percentage(),
+            habit: None,
+        }
+    }
+}
+
+/// Percentage of the distance travelled from `start` towards `target`. Works for
+/// decreasing targets too, e.g. going from 90 down to 80.
+pub fn progress_percentage(start: f64, target: f64, current: f64) -> f64 {
+    if target == start {
+        return if current == target { 100.0 } else { 0.0 };
+    }
+    (current - start) / (target - start) * 100.0
 }
 
+// The current value is the number of records on top of the start value for "count"
+// targets, the latest record by entry date for "value" targets, and the total of all
+// record values on top of the start value for "sum" targets. Habits only count records
+// from the current period, which is the last one once the target date has passed, the
+// same as `HabitProgress::for_target`.
+const SELECT_TARGETS: &str = "
+    SELECT
+        t.*,
+        CASE
+            WHEN t.period IS NOT NULL THEN (
+                SELECT CASE t.target_type WHEN 'count' THEN CAST(COUNT(*) AS REAL) ELSE COALESCE(SUM(pr.value), 0.0) END
+                FROM progress_records pr, (
+                    SELECT CASE t.period
+                        WHEN 'daily' THEN day
+                        WHEN 'weekly' THEN date(day, '-6 days', 'weekday 1')
+                        WHEN 'monthly' THEN date(day, 'start of month')
+                    END AS start
+                    FROM (SELECT min(date('now'), t.target_date) AS day)
+                ) p
+                WHERE pr.target_id = t.id
+                    AND pr.entry_date >= p.start
+                    AND pr.entry_date < date(p.start, CASE t.period
+                        WHEN 'daily' THEN '+1 day'
+                        WHEN 'weekly' THEN '+7 days'
+                        WHEN 'monthly' THEN '+1 month'
+                    END)
+            )
+            ELSE CASE t.target_type
+            WHEN 'count' THEN t.start_value + (
+                SELECT COUNT(*) FROM progress_records pr WHERE pr.target_id = t.id
+            )
+            WHEN 'value' THEN COALESCE((
+                SELECT pr.value FROM progress_records pr
+                WHERE pr.target_id = t.id AND pr.value IS NOT NULL
+                ORDER BY pr.entry_date DESC, pr.id DESC
+                LIMIT 1
+            ), t.start_value)
+            WHEN 'sum' THEN t.start_value + (
+                SELECT COALESCE(SUM(pr.value), 0) FROM progress_records pr WHERE pr.target_id = t.id
+            )
+            ELSE t.start_value
+        END END AS current_value,
+        (
+            SELECT MAX(tsc.changed_at) FROM target_status_changes tsc WHERE tsc.target_id = t.id
+        ) AS status_changed_at
+    FROM targets t";
+
 #[derive(Default, Props)]
 pub struct TargetsTableProps<'a> {
     pub targets: Option<&'a Vec<Target>>,
@@ -59,49 +235,72 @@ pub fn TargetsTable<'a>(props: &TargetsTableProps<'a>) -> impl Into<AnyElement<'
             }
 
             View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
-                View(width: 10pct, justify_content: JustifyContent::Center) {
+                View(width: 5pct, justify_content: JustifyContent::Center) {
                     Text(content: "id", weight: Weight::Bold, decoration: TextDecoration::Underline)
                 }
 
-                View(width: 25pct, justify_content: JustifyContent::Center) {
+                View(width: 20pct, justify_content: JustifyContent::Center) {
                     Text(content: "name", weight: Weight::Bold, decoration: TextDecoration::Underline)
                 }
 
-                View(width: 12.5pct, justify_content: JustifyContent::Center) {
+                View(width: 5pct, justify_content: JustifyContent::Center) {
+                    Text(content: "year", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+
+                View(width: 15pct, justify_content: JustifyContent::Center) {
                     Text(content: "target date", weight: Weight::Bold, decoration: TextDecoration::Underline)
                 }
-                View(width: 12.5pct, justify_content: JustifyContent::Center) {
+                View(width: 10pct, justify_content: JustifyContent::Center) {
                     Text(content: "status", weight: Weight::Bold, decoration: TextDecoration::Underline)
                 }
-                View(width: 12.5pct, justify_content: JustifyContent::Center) {
+                View(width: 10pct, justify_content: JustifyContent::Center) {
                     Text(content: "start", weight: Weight::Bold, decoration: TextDecoration::Underline)
                 }
-                View(width: 12.5pct, justify_content: JustifyContent::Center) {
+                View(width: 10pct, justify_content: JustifyContent::Center) {
+                    Text(content: "current", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
+                View(width: 10pct, justify_content: JustifyContent::Center) {
                     Text(content: "target", weight: Weight::Bold, decoration: TextDecoration::Underline)
                 }
+                View(width: 15pct, justify_content: JustifyContent::Center) {
+                    Text(content: "progress", weight: Weight::Bold, decoration: TextDecoration::Underline)
+                }
             }
 
             #(props.targets.map(|targets| targets.iter().enumerate().map(|(i, target)| element! {
                 View(background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) }) {
-                    View(width: 10pct, justify_content: JustifyContent::Center) {
+                    View(width: 5pct, justify_content: JustifyContent::Center) {
                         Text(content: target.id.to_string())
                     }
 
-                    View(width: 25pct, justify_content: JustifyContent::Center) {
+                    View(width: 20pct, justify_content: JustifyContent::Center) {
                         Text(content: target.name.clone())
                     }
 
-                    View(width: 12.5pct, justify_content: JustifyContent::Center) {
+                    View(width: 5pct, justify_content: JustifyContent::Center) {
+                        Text(content: target.year.to_string())
+                    }
+
+                    View(width: 15pct, justify_content: JustifyContent::Center) {
                         Text(content: target.target_date.to_string())
                     }
-                    View(width: 12.5pct, justify_content: JustifyContent::Center) {
+                    View(width: 10pct, justify_content: JustifyContent::Center) {
                         Text(content: target.status.to_string())
                     }
-                    View(width: 12.5pct, justify_content: JustifyContent::Center) {
+                    View(width: 10pct, justify_content: JustifyContent::Center) {
                         Text(content: target.start_value.to_string())
                     }
-                    View(width: 12.5pct, justify_content: JustifyContent::Center) {
-                        Text(content: target.target_value.to_string())
+                    View(width: 10pct, justify_content: JustifyContent::Center) {
+                        Text(content: target.current_value.to_string())
+                    }
+                    View(width: 10pct, justify_content: JustifyContent::Center) {
+                        Text(content: match target.period {
+                            Some(period) => format!("{}/{}", target.target_value, period.unit()),
+                            None => target.target_value.to_string(),
+                        })
+                    }
+                    View(width: 15pct, justify_content: JustifyContent::Center) {
+                        Text(content: format!("{:.1}%", target.progress_percentage()))
                     }
                 }
             })).into_iter().flatten())
@@ -109,139 +308,588 @@ pub fn TargetsTable<'a>(props: &TargetsTableProps<'a>) -> impl Into<AnyElement<'
     }
 }
 
-pub async fn get_targets(db: &Pool<Sqlite>) -> Vec<Target> {
-    sqlx::query_as::<_, Target>("SELECT * FROM targets")
-        .fetch_all(db)
-        .await
-        .unwrap()
+#[derive(Default, Props)]
+pub struct TargetDetailProps<'a> {
+    pub target: Option<&'a Target>,
+    /// Shown in place of the pace for habits.
+    pub habit: Option<HabitProgress>,
 }
 
-pub async fn get_target(db: &Pool<Sqlite>, id: &i64) -> Target {
-    sqlx::query_as::<_, Target>("SELECT * FROM targets WHERE id=$1")
-        .bind(id)
-        .fetch_one(db)
-        .await
-        .unwrap()
+#[component]
+pub fn TargetDetail<'a>(props: &TargetDetailProps<'a>) -> impl Into<AnyElement<'a>> {
+    let target = props.target.unwrap();
+    let rows = vec![
+        ("id", target.id.to_string()),
+        ("year", target.year.to_string()),
+        ("type", target.target_type.to_string()),
+        ("status", target.status.to_string()),
+        (
+            "status changed",
+            target
+                .status_changed_at
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "N/A".to_string()),
+        ),
+        ("auto complete", target.auto_complete.to_string()),
+        (
+            "period",
+            target
+                .period
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "N/A".to_string()),
+        ),
+        ("created", target.created_at.date().to_string()),
+        ("target date", target.target_date.to_string()),
+        ("start", target.start_value.to_string()),
+        ("current", target.current_value.to_string()),
+        ("target", target.target_value.to_string()),
+    ];
+
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            padding_left: 2,
+            padding_right: 2,
+            flex_direction: FlexDirection::Column,
+            width: 100,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom: 1) {
+                Text(content: target.name.clone(), weight: Weight::Bold)
+            }
+            #(rows.into_iter().map(|(label, value)| element! {
+                View {
+                    View(width: 20) {
+                        Text(content: label, weight: Weight::Bold)
+                    }
+                    Text(content: value)
+                }
+            }))
+            View(margin_top: 1, margin_bottom: 1) {
+                progress_bar::StaticProgressBar(progress_percentage: target.progress_percentage(), target: format!("{:.1}%", target.progress_percentage()), title: "progress")
+            }
+            #(match &props.habit {
+                Some(habit) => element!(HabitDetail(habit: habit.clone())).into_any(),
+                None => element!(PaceDetail(pace: target.pace())).into_any(),
+            })
+        }
+    }
 }
 
-pub async fn create_target(
+/// Gets targets with the given status and year, leaving out either filter that's `None`.
+pub async fn get_targets(
     db: &Pool<Sqlite>,
-    name: &String,
-    target_date: &Option<NaiveDate>,
-    target_type: TargetType,
-    start_value: &Option<f64>,
-    target_value: &f64,
-) -> Target {
-    let last_date_this_year =
-        chrono::NaiveDate::from_ymd_opt(chrono::Utc::now().year(), 12, 31).unwrap();
-
-    sqlx::query_as::<_, Target>(
-        "INSERT INTO targets (name, target_date, status,target_type, start_value, target_value)
-                        VALUES ($1, $2, $3, $4, $5, $6)
-                        RETURNING *;",
-    )
-    .bind(name)
-    .bind(match target_date {
-        Some(x) => x,
-        None => &last_date_this_year,
-    })
-    .bind("active")
-    .bind(target_type)
-    .bind(match start_value {
+    status: Option<&TargetStatus>,
+    year: Option<i32>,
+) -> Result<Vec<Target>> {
+    let targets = sqlx::query_as::<_, Target>(&format!(
+        "{} WHERE ($1 IS NULL OR t.status = $1) AND ($2 IS NULL OR t.year = $2)",
+        SELECT_TARGETS
+    ))
+    .bind(status)
+    .bind(year)
+    .fetch_all(db)
+    .await?;
+    Ok(targets)
+}
+
+pub async fn get_target<'e, E>(db: E, id: &i64) -> Result<Target>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query_as::<_, Target>(&format!("{} WHERE t.id=$1", SELECT_TARGETS))
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("No target with id {}", id)))
+}
+
+/// The fields of a target to create.
+pub struct NewTarget {
+    pub name: String,
+    /// Defaults to the end of the target's year.
+    pub target_date: Option<NaiveDate>,
+    pub target_type: TargetType,
+    /// Defaults to 0.
+    pub start_value: Option<f64>,
+    pub target_value: f64,
+    pub auto_complete: bool,
+    pub period: Option<Period>,
+    /// Defaults to the year of the target date, or this year without one.
+    pub year: Option<i32>,
+    /// Defaults to now.
+    pub created_at: Option<NaiveDateTime>,
+}
+
+/// Works with a pool or an open transaction, e.g. to create targets along with their records.
+pub async fn create_target<'a, A>(db: A, new_target: &NewTarget) -> Result<Target>
+where
+    A: Acquire<'a, Database = Sqlite>,
+{
+    let year = new_target
+        .year
+        .or(new_target.target_date.map(|x| x.year()))
+        .unwrap_or_else(years::this_year);
+    let target_date = match new_target.target_date {
         Some(x) => x,
-        None => &0.0,
-    })
-    .bind(target_value)
-    .fetch_one(db)
-    .await
-    .unwrap()
+        None => years::last_day_of_year(year)?,
+    };
+    let start_value = new_target.start_value.unwrap_or(0.0);
+    if new_target.period.is_some() {
+        habits::validate_habit(
+            &new_target.target_type,
+            start_value,
+            new_target.auto_complete,
+        )?;
+    }
+
+    let mut connection = db.acquire().await?;
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO targets (name, target_date, status,target_type, start_value, target_value, auto_complete, period, year, created_at)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, COALESCE($10, CURRENT_TIMESTAMP))
+                        RETURNING id;",
+    )
+    .bind(&new_target.name)
+    .bind(target_date)
+    .bind(TargetStatus::Active)
+    .bind(&new_target.target_type)
+    .bind(start_value)
+    .bind(new_target.target_value)
+    .bind(new_target.auto_complete)
+    .bind(new_target.period)
+    .bind(year)
+    .bind(new_target.created_at)
+    .fetch_one(&mut *connection)
+    .await?;
+
+    get_target(&mut *connection, &id).await
+}
+
+/// Finds a target from something a person typed: an id, a name, or a unique prefix
+/// of a name, ignoring case. Suggests close names when nothing matches.
+pub async fn resolve_target(db: &Pool<Sqlite>, reference: &str) -> Result<Target> {
+    if let Ok(id) = reference.parse::<i64>() {
+        match get_target(db, &id).await {
+            Err(Error::NotFound(_)) => {}
+            result => return result,
+        }
+    }
+
+    let targets = get_targets(db, None, None).await?;
+    let reference_lower = reference.to_lowercase();
+
+    let exact_matches: Vec<&Target> = targets
+        .iter()
+        .filter(|target| target.name.to_lowercase() == reference_lower)
+        .collect();
+    if let Some(target) = years::pick_year(&exact_matches) {
+        return Ok(target.clone());
+    }
+
+    let prefix_matches: Vec<&Target> = targets
+        .iter()
+        .filter(|target| target.name.to_lowercase().starts_with(&reference_lower))
+        .collect();
+    let same_name = prefix_matches
+        .windows(2)
+        .all(|x| x[0].name.to_lowercase() == x[1].name.to_lowercase());
+    match prefix_matches.as_slice() {
+        [] => {}
+        matches if same_name => return Ok(years::pick_year(matches).unwrap().clone()),
+        matches => {
+            return Err(Error::Validation(format!(
+                "\"{}\" matches more than one target, did you mean {}?",
+                reference,
+                quoted_names(matches.iter().copied())
+            )))
+        }
+    }
+
+    let mut suggestions: Vec<(usize, &Target)> = targets
+        .iter()
+        .map(|target| {
+            (
+                edit_distance(&reference_lower, &target.name.to_lowercase()),
+                target,
+            )
+        })
+        .filter(|(distance, _)| *distance <= 3)
+        .collect();
+    suggestions.sort_by_key(|(distance, _)| *distance);
+
+    if suggestions.is_empty() {
+        Err(Error::NotFound(format!(
+            "No target matching \"{}\"",
+            reference
+        )))
+    } else {
+        Err(Error::NotFound(format!(
+            "No target matching \"{}\", did you mean {}?",
+            reference,
+            quoted_names(suggestions.into_iter().take(3).map(|(_, target)| target))
+        )))
+    }
+}
+
+fn quoted_names<'a>(targets: impl Iterator<Item = &'a Target>) -> String {
+    targets
+        .map(|target| format!("\"{}\"", target.name))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+// Levenshtein distance, for suggesting names when there's a typo
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The fields of a target to change. Anything left as `None` is kept as it is.
+#[derive(Default)]
+pub struct TargetChanges {
+    pub name: Option<String>,
+    pub target_date: Option<NaiveDate>,
+    pub target_type: Option<TargetType>,
+    pub start_value: Option<f64>,
+    pub target_value: Option<f64>,
+    pub auto_complete: Option<bool>,
+    pub period: Option<Period>,
+    pub year: Option<i32>,
+}
+
+pub async fn update_target(db: &Pool<Sqlite>, id: &i64, changes: &TargetChanges) -> Result<Target> {
+    // Checked and changed in one transaction, so no records can be added in between
+    let mut tx = db.begin().await?;
+    let target = get_target(&mut *tx, id).await?;
+    if changes.period.or(target.period).is_some() {
+        habits::validate_habit(
+            changes.target_type.as_ref().unwrap_or(&target.target_type),
+            changes.start_value.unwrap_or(target.start_value),
+            changes.auto_complete.unwrap_or(target.auto_complete),
+        )?;
+    }
+    // Existing records have to make sense for the new target type
+    if let Some(target_type) = &changes.target_type {
+        for progress_record in
+            progress_records::get_progress_records_for_target(&mut *tx, id).await?
+        {
+            progress_records::validate_progress_record(
+                target_type,
+                &progress_record.item_name,
+                &progress_record.value,
+            )?;
+        }
+    }
+
+    sqlx::query(
+        "UPDATE targets
+            SET name = COALESCE($2, name),
+                target_date = COALESCE($3, target_date),
+                target_type = COALESCE($4, target_type),
+                start_value = COALESCE($5, start_value),
+                target_value = COALESCE($6, target_value),
+                auto_complete = COALESCE($7, auto_complete),
+                period = COALESCE($8, period),
+                year = COALESCE($9, year)
+            WHERE id = $1",
+    )
+    .bind(id)
+    .bind(&changes.name)
+    .bind(changes.target_date)
+    .bind(&changes.target_type)
+    .bind(changes.start_value)
+    .bind(changes.target_value)
+    .bind(changes.auto_complete)
+    .bind(changes.period)
+    .bind(changes.year)
+    .execute(&mut *tx)
+    .await?;
+
+    let target = get_target(&mut *tx, id).await?;
+    tx.commit().await?;
+    Ok(target)
 }
 
-pub async fn delete_target(db: &Pool<Sqlite>, id: &i64) {
-    sqlx::query("DELETE FROM targets WHERE id=$1")
+/// Changes the status of a target, keeping a record of when it happened.
+pub async fn set_target_status(
+    db: &Pool<Sqlite>,
+    id: &i64,
+    status: TargetStatus,
+) -> Result<Target> {
+    let mut tx = db.begin().await?;
+    let result = sqlx::query("UPDATE targets SET status = $2 WHERE id = $1")
         .bind(id)
-        .execute(db)
-        .await
-        .unwrap();
-    println!("Target deleted");
+        .bind(&status)
+        .execute(&mut *tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound(format!("No target with id {}", id)));
+    }
+    sqlx::query("INSERT INTO target_status_changes (target_id, status) VALUES ($1, $2)")
+        .bind(id)
+        .bind(&status)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    get_target(db, id).await
+}
+
+/// Marks an active target as completed if it has auto complete turned on and has
+/// reached 100%. Returns the target if it was completed.
+pub async fn auto_complete_target(db: &Pool<Sqlite>, id: &i64) -> Result<Option<Target>> {
+    let target = get_target(db, id).await?;
+    if target.auto_complete
+        && target.status == TargetStatus::Active
+        && target.progress_percentage() >= 100.0
+    {
+        Ok(Some(
+            set_target_status(db, id, TargetStatus::Completed).await?,
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+/// What to do with a target's progress records when it's deleted.
+pub enum DeleteMode {
+    /// Don't delete the target if it has any progress records.
+    Refuse,
+    /// Delete the progress records along with the target.
+    Cascade,
+    /// Move the progress records to the target with this id.
+    Reassign(i64),
 }
 
-#[derive(Debug)]
+/// Deletes a target, returning how many progress records were deleted or reassigned.
+pub async fn delete_target(db: &Pool<Sqlite>, id: &i64, mode: DeleteMode) -> Result<u64> {
+    let mut tx = db.begin().await?;
+    let affected = match mode {
+        DeleteMode::Refuse => 0,
+        DeleteMode::Cascade => sqlx::query("DELETE FROM progress_records WHERE target_id=$1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected(),
+        DeleteMode::Reassign(new_target_id) => {
+            sqlx::query("UPDATE progress_records SET target_id=$2 WHERE target_id=$1")
+                .bind(id)
+                .bind(new_target_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected()
+        }
+    };
+
+    // With foreign keys on, this fails rather than orphaning any records left behind
+    let result = sqlx::query("DELETE FROM targets WHERE id=$1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound(format!("No target with id {}", id)));
+    }
+    tx.commit().await?;
+
+    Ok(affected)
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct TargetProgress {
     pub target_id: i64,
     pub percentage: f64,
     pub name: String,
+    pub current_value: f64,
     pub target_value: f64,
+    pub pace: Pace,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub habit: Option<HabitProgress>,
+    /// How progress has grown over time, for the dashboard.
+    #[serde(skip)]
+    pub sparkline: String,
 }
 
-pub async fn get_progress_for_all_targets(db: &Pool<Sqlite>) -> Vec<TargetProgress> {
-    let rows = sqlx::query!(
-        r#"
-        WITH progress_values AS (
-            SELECT 
-                t.id as target_id,
-                t.target_type as target_type,
-                t.target_value as target_value,
-                t.start_value as start_value,
-                t.name as name,
-                CASE 
-                    WHEN t.target_type = 'count' THEN CAST(COUNT(pr.id) AS FLOAT)
-                    WHEN t.target_type = 'value' THEN COALESCE(MAX(pr.value), t.start_value)
-                    ELSE 0 
-                END as current_value
-            FROM targets t
-            LEFT JOIN progress_records pr ON t.id = pr.target_id
-            GROUP BY t.id
-        )
-        SELECT 
-            target_id,
-            CAST(current_value AS FLOAT) / CAST(target_value AS FLOAT) as percentage,
-            name,
-            target_value
-        FROM progress_values
-        "#
-    )
-    .fetch_all(db)
-    .await
-    .unwrap_or_default();
-
-    rows.into_iter()
-        .map(|row| TargetProgress {
-            target_id: row.target_id.unwrap(),
-            percentage: row.percentage.unwrap() * 100.0,
-            name: row.name.unwrap(),
-            target_value: row.target_value.unwrap(),
-        })
-        .collect()
-}
-
-// maybe in the future
-// pub async fn get_progress_for_target(db: &Pool<Sqlite>, target_id: i64) -> f64 {
-//     let result = sqlx::query!(
-//         r#"
-//         WITH progress_value AS (
-//             SELECT
-//                 t.target_type as target_type,
-//                 t.target_value as target_value,
-//                 t.start_value as start_value,
-//                 CASE
-//                     WHEN t.target_type = 'Count' THEN CAST(COUNT(pr.id) AS FLOAT)
-//                     WHEN t.target_type = 'Value' THEN COALESCE(MAX(pr.value), t.start_value)
-//                     ELSE 0
-//                 END as current_value
-//             FROM targets t
-//             LEFT JOIN progress_records pr ON t.id = pr.target_id
-//             WHERE t.id = ?
-//             GROUP BY t.id
-//         )
-//         SELECT CAST(current_value AS FLOAT) / CAST(target_value AS FLOAT) as percentage
-//         FROM progress_value
-//         "#,
-//         target_id
-//     )
-//     .fetch_optional(db)
-//     .await
-//     .unwrap_or_default();
-
-//     result.map(|r| r.percentage.unwrap_or(0.0)).unwrap_or(0.0)
-// }
+impl TargetProgress {
+    pub fn new(target: Target, habit: Option<HabitProgress>, sparkline: String) -> Self {
+        TargetProgress {
+            target_id: target.id,
+            percentage: target.progress_percentage(),
+            pace: target.pace(),
+            name: target.name,
+            current_value: target.current_value,
+            target_value: target.target_value,
+            habit,
+            sparkline,
+        }
+    }
+}
+
+pub async fn get_progress_for_all_targets(
+    db: &Pool<Sqlite>,
+    status: Option<&TargetStatus>,
+    year: Option<i32>,
+) -> Result<Vec<TargetProgress>> {
+    let today = years::today();
+    let mut target_progresses = Vec::new();
+    for target in get_targets(db, status, year).await? {
+        let progress_records =
+            progress_records::get_progress_records_for_target(db, &target.id).await?;
+        let habit = HabitProgress::for_target(&target, &progress_records, today);
+        let sparkline = chart::sparkline(&target, &progress_records, today);
+        target_progresses.push(TargetProgress::new(target, habit, sparkline));
+    }
+    Ok(target_progresses)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::database;
+
+    /// A "count" target made on `created_at` that has got from `start_value` to `current_value`.
+    pub fn target(
+        created_at: NaiveDate,
+        target_date: NaiveDate,
+        start_value: f64,
+        target_value: f64,
+        current_value: f64,
+    ) -> Target {
+        Target {
+            id: 1,
+            name: String::from("films"),
+            created_at: created_at.and_hms_opt(0, 0, 0).unwrap(),
+            target_date,
+            status: TargetStatus::Active,
+            start_value,
+            target_value,
+            target_type: TargetType::Count,
+            auto_complete: false,
+            period: None,
+            year: target_date.year(),
+            current_value,
+            status_changed_at: None,
+        }
+    }
+
+    #[test]
+    fn progress_percentage_of_increasing_target() {
+        assert_eq!(progress_percentage(0.0, 50.0, 0.0), 0.0);
+        assert_eq!(progress_percentage(0.0, 50.0, 20.0), 40.0);
+        assert_eq!(progress_percentage(10.0, 20.0, 15.0), 50.0);
+        assert_eq!(progress_percentage(0.0, 50.0, 60.0), 120.0);
+    }
+
+    #[test]
+    fn progress_percentage_of_decreasing_target() {
+        assert_eq!(progress_percentage(90.0, 80.0, 90.0), 0.0);
+        assert_eq!(progress_percentage(90.0, 80.0, 85.0), 50.0);
+        assert_eq!(progress_percentage(90.0, 80.0, 80.0), 100.0);
+        // Going the wrong way is negative progress
+        assert_eq!(progress_percentage(90.0, 80.0, 95.0), -50.0);
+    }
+
+    #[test]
+    fn progress_percentage_when_start_is_target() {
+        assert_eq!(progress_percentage(10.0, 10.0, 10.0), 100.0);
+        assert_eq!(progress_percentage(10.0, 10.0, 12.0), 0.0);
+    }
+
+    async fn create_named(db: &Pool<Sqlite>, name: &str, year: i32) -> Target {
+        let new_target = NewTarget {
+            name: String::from(name),
+            target_date: None,
+            target_type: TargetType::Count,
+            start_value: None,
+            target_value: 10.0,
+            auto_complete: false,
+            period: None,
+            year: Some(year),
+            created_at: None,
+        };
+        create_target(db, &new_target).await.unwrap()
+    }
+
+    fn error_message(result: Result<Target>) -> String {
+        match result {
+            Err(Error::Validation(x) | Error::NotFound(x)) => x,
+            x => panic!("expected an error, got {:?}", x.map(|x| x.name)),
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_target_by_name_or_prefix() {
+        let db = database::open_test_db().await.unwrap();
+        let this_year = years::this_year();
+        create_named(&db, "Running", this_year).await;
+        let reading = create_named(&db, "reading", this_year).await;
+        let last_years_films = create_named(&db, "films", this_year - 1).await;
+        let films = create_named(&db, "films", this_year).await;
+
+        assert_eq!(resolve_target(&db, "READING").await.unwrap().id, reading.id);
+        assert_eq!(resolve_target(&db, "rea").await.unwrap().id, reading.id);
+        // Targets rolled over share a name, and this year's is picked
+        assert_eq!(resolve_target(&db, "fi").await.unwrap().id, films.id);
+        assert_eq!(
+            resolve_target(&db, &last_years_films.id.to_string())
+                .await
+                .unwrap()
+                .id,
+            last_years_films.id
+        );
+
+        assert_eq!(
+            error_message(resolve_target(&db, "r").await),
+            "\"r\" matches more than one target, did you mean \"Running\" or \"reading\"?"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_target_ids_before_numeric_names() {
+        let db = database::open_test_db().await.unwrap();
+        let this_year = years::this_year();
+        let films = create_named(&db, "films", this_year).await;
+        // Named after the id of "films", which still wins
+        create_named(&db, &films.id.to_string(), this_year).await;
+        let named_2024 = create_named(&db, "2024", this_year).await;
+
+        assert_eq!(
+            resolve_target(&db, &films.id.to_string()).await.unwrap().id,
+            films.id
+        );
+        // Without a target with that id, the name is used
+        assert_eq!(resolve_target(&db, "2024").await.unwrap().id, named_2024.id);
+    }
+
+    #[tokio::test]
+    async fn resolve_target_suggests_close_names() {
+        let db = database::open_test_db().await.unwrap();
+        let this_year = years::this_year();
+        create_named(&db, "running", this_year).await;
+        create_named(&db, "rowing", this_year).await;
+        create_named(&db, "films", this_year).await;
+
+        assert_eq!(
+            error_message(resolve_target(&db, "runing").await),
+            "No target matching \"runing\", did you mean \"running\" or \"rowing\"?"
+        );
+        assert_eq!(
+            error_message(resolve_target(&db, "flims").await),
+            "No target matching \"flims\", did you mean \"films\"?"
+        );
+        assert_eq!(
+            error_message(resolve_target(&db, "swimming").await),
+            "No target matching \"swimming\""
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use chrono::{Datelike, Months, NaiveDate};
+use iocraft::prelude::*;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::chart;
+use crate::error::{Error, Result};
+use crate::progress_records;
+use crate::targets::{self, NewTarget, Target, TargetStatus, TargetType};
+
+/// Today in UTC, the same as the database's `CURRENT_TIMESTAMP` and `date('now')`, so
+/// records, pace, habits and the dashboard all agree on which day it is.
+pub fn today() -> NaiveDate {
+    chrono::Utc::now().date_naive()
+}
+
+pub fn this_year() -> i32 {
+    today().year()
+}
+
+pub fn first_day_of_year(year: i32) -> Result<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, 1, 1)
+        .ok_or_else(|| Error::Validation(format!("{} isn't a valid year", year)))
+}
+
+pub fn last_day_of_year(year: i32) -> Result<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, 12, 31)
+        .ok_or_else(|| Error::Validation(format!("{} isn't a valid year", year)))
+}
+
+/// Targets rolled over from one year to the next share a name, so of several that do
+/// this picks this year's, or the latest year's when there isn't one for this year.
+pub fn pick_year<'a>(targets: &[&'a Target]) -> Option<&'a Target> {
+    let this_year = this_year();
+    targets
+        .iter()
+        .copied()
+        .max_by_key(|target| (target.year == this_year, target.year))
+}
+
+pub struct Rollover {
+    pub created: Vec<Target>,
+    /// Targets the next year already has one with the same name for.
+    pub skipped: Vec<Target>,
+}
+
+/// Copies a year's active and completed targets into the next year with fresh progress,
+/// in one transaction. "value" targets start from where they got to, and target dates
+/// move on a year.
+pub async fn rollover(db: &Pool<Sqlite>, year: i32) -> Result<Rollover> {
+    let next_year = year + 1;
+    let start_of_next_year = first_day_of_year(next_year)?.and_hms_opt(0, 0, 0).unwrap();
+    let existing_names: Vec<String> = targets::get_targets(db, None, Some(next_year))
+        .await?
+        .into_iter()
+        .map(|x| x.name.to_lowercase())
+        .collect();
+
+    let year_targets = targets::get_targets(db, None, Some(year)).await?;
+
+    let mut created = Vec::new();
+    let mut skipped = Vec::new();
+    let mut tx = db.begin().await?;
+    for target in year_targets {
+        if !matches!(
+            target.status,
+            TargetStatus::Active | TargetStatus::Completed
+        ) {
+            continue;
+        }
+        if existing_names.contains(&target.name.to_lowercase()) {
+            skipped.push(target);
+            continue;
+        }
+
+        let new_target = NewTarget {
+            name: target.name.clone(),
+            target_date: target.target_date.checked_add_months(Months::new(12)),
+            target_type: target.target_type.clone(),
+            start_value: Some(match target.target_type {
+                TargetType::Value => target.current_value,
+                TargetType::Count | TargetType::Sum => target.start_value,
+            }),
+            target_value: target.target_value,
+            auto_complete: target.auto_complete,
+            period: target.period,
+            year: Some(next_year),
+            // The pace for the new year is measured from its start, even when rolling over early
+            created_at: Some(start_of_next_year),
+        };
+        created.push(targets::create_target(&mut *tx, &new_target).await?);
+    }
+    tx.commit().await?;
+    Ok(Rollover { created, skipped })
+}
+
+/// A target alongside the one with the same name from the year before.
+#[derive(Clone, Debug, Serialize)]
+pub struct YearComparison {
+    pub name: String,
+    pub last_year_target_id: i64,
+    pub target_id: i64,
+    /// Where last year's target had got to by the same day of the year as this one has now.
+    pub last_year_to_date: f64,
+    /// Where last year's target ended up.
+    pub last_year_final: f64,
+    /// Habits are left out, as they start again each period.
+    pub last_year_percentage: Option<f64>,
+    pub value: f64,
+    pub percentage: Option<f64>,
+    /// This year's value less last year's at the same point.
+    pub change: f64,
+    /// Whether this year is doing at least as well as last year at the same point, which
+    /// for a decreasing target means a change that's negative or nothing.
+    pub improved: bool,
+}
+
+/// Compares each of a year's targets with the target of the same name from the year
+/// before, if there is one. Habits are compared by their totals.
+pub async fn compare_years(db: &Pool<Sqlite>, year: i32) -> Result<Vec<YearComparison>> {
+    let today = today();
+    // The same point in the year as today, or its end or start for other years
+    let as_of = today.clamp(first_day_of_year(year)?, last_day_of_year(year)?);
+    let last_year_as_of = as_of
+        .checked_sub_months(Months::new(12))
+        .ok_or_else(|| Error::Validation(format!("{} isn't a valid year", year - 1)))?;
+    let last_year_targets = targets::get_targets(db, None, Some(year - 1)).await?;
+
+    let mut comparisons = Vec::new();
+    for target in targets::get_targets(db, None, Some(year)).await? {
+        let Some(last_year_target) = last_year_targets
+            .iter()
+            .find(|x| x.name.to_lowercase() == target.name.to_lowercase())
+        else {
+            continue;
+        };
+
+        let progress_records =
+            progress_records::get_progress_records_for_target(db, &target.id).await?;
+        let series = chart::progress_series(&target, &progress_records);
+        let last_year_progress_records =
+            progress_records::get_progress_records_for_target(db, &last_year_target.id).await?;
+        let last_year_series =
+            chart::progress_series(last_year_target, &last_year_progress_records);
+
+        let value = chart::value_on(&target, &series, as_of);
+        let last_year_to_date =
+            chart::value_on(last_year_target, &last_year_series, last_year_as_of);
+        let last_year_final = last_year_series
+            .last()
+            .map_or(last_year_target.start_value, |x| x.value);
+        let percentage = |target: &Target, value: f64| {
+            target.period.is_none().then(|| {
+                targets::progress_percentage(target.start_value, target.target_value, value)
+            })
+        };
+        comparisons.push(YearComparison {
+            name: target.name.clone(),
+            last_year_target_id: last_year_target.id,
+            target_id: target.id,
+            last_year_to_date,
+            last_year_final,
+            last_year_percentage: percentage(last_year_target, last_year_final),
+            value,
+            percentage: percentage(&target, value),
+            change: value - last_year_to_date,
+            improved: (value - last_year_to_date) * target.direction() >= 0.0,
+        });
+    }
+    Ok(comparisons)
+}
+
+#[derive(Default, Props)]
+pub struct YearComparisonTableProps<'a> {
+    pub comparisons: Option<&'a Vec<YearComparison>>,
+    pub title: &'a str,
+}
+
+#[component]
+pub fn YearComparisonTable<'a>(props: &YearComparisonTableProps<'a>) -> impl Into<AnyElement<'a>> {
+    let with_percentage = |value: f64, percentage: Option<f64>| match percentage {
+        Some(percentage) => format!("{} ({:.1}%)", value, percentage),
+        None => value.to_string(),
+    };
+    let columns: [(&str, u16); 5] = [
+        ("name", 25),
+        ("last year by now", 20),
+        ("last year final", 20),
+        ("this year", 20),
+        ("change", 15),
+    ];
+    let rows: Vec<([String; 5], bool)> = props
+        .comparisons
+        .map(|comparisons| {
+            comparisons
+                .iter()
+                .map(|x| {
+                    (
+                        [
+                            x.name.clone(),
+                            x.last_year_to_date.to_string(),
+                            with_percentage(x.last_year_final, x.last_year_percentage),
+                            with_percentage(x.value, x.percentage),
+                            format!("{:+}", x.change),
+                        ],
+                        x.improved,
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    element! {
+        View(
+            margin_top: 1,
+            margin_bottom: 1,
+            flex_direction: FlexDirection::Column,
+            width: 100,
+            border_style: BorderStyle::Round,
+            border_color: Color::Cyan,
+        ) {
+            View(width: 100pct, justify_content: JustifyContent::Center, margin_bottom:1, ) {
+                Text(content: props.title, weight: Weight::Bold )
+            }
+
+            View(border_style: BorderStyle::Single, border_edges: Edges::Bottom, border_color: Color::Grey) {
+                #(columns.iter().map(|(label, width)| element! {
+                    View(width: Percent(*width as f32), justify_content: JustifyContent::Center) {
+                        Text(content: *label, weight: Weight::Bold, decoration: TextDecoration::Underline)
+                    }
+                }))
+            }
+
+            #(rows.into_iter().enumerate().map(|(i, (row, improved))| element! {
+                View(background_color: if i % 2 == 0 { None } else { Some(Color::DarkGrey) }) {
+                    #(row.into_iter().zip(columns).enumerate().map(|(column, (value, (_, width)))| element! {
+                        View(width: Percent(width as f32), justify_content: JustifyContent::Center) {
+                            Text(
+                                content: value,
+                                color: match (column, improved) {
+                                    (4, true) => Some(Color::Green),
+                                    (4, false) => Some(Color::Red),
+                                    _ => None,
+                                },
+                            )
+                        }
+                    }))
+                }
+            }))
+        }
+    }
+}